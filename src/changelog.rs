@@ -1,46 +1,1235 @@
-use clog::fmt::MarkdownWriter;
-use clog::Clog;
-use std::io::BufWriter;
-use std::path::PathBuf;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-pub fn write(repository_path: &str, old_version: &str, new_version: &str) -> Result<(), String> {
-    let mut clog = Clog::with_dir(repository_path).map_err(|_| "Clog failed".to_owned())?;
+use chrono::{FixedOffset, TimeZone, Utc};
+use git2::Repository;
+use handlebars::{handlebars_helper, no_escape, Handlebars};
+use semver::Version;
+use serde::Serialize;
 
-    let mut clog_file = PathBuf::from(repository_path);
-    clog_file.push("Changelog.md");
+use crate::conventional_commit::ConventionalCommit;
+use crate::git::{self, CommitFilter};
+use crate::workspace::{Package, Release};
 
-    // TODO: Make this configurable? Rely on clog's own configuration?
-    clog.changelog(clog_file.to_str().unwrap())
-        .from(format!("v{}", old_version))
-        .version(format!("v{}", new_version));
+/// Sections of the changelog by commit type, in the order they are written.
+/// Breaking changes always come first.
+const SECTIONS: &[(&str, &str)] = &[
+    ("feat", "Features"),
+    ("fix", "Bug Fixes"),
+    ("perf", "Performance"),
+];
 
-    clog.write_changelog()
-        .map_err(|_| "Failed to write Changelog.md".to_owned())
+/// Sections of a Keep a Changelog changelog by their commit types.
+/// Breaking changes are listed first under "Changed", and only there.
+const KEEP_A_CHANGELOG_SECTIONS: &[(&str, &[&str])] = &[
+    ("Added", &["feat"]),
+    ("Changed", &["perf", "refactor"]),
+    ("Removed", &["remove"]),
+    ("Fixed", &["fix"]),
+];
+
+const KEEP_A_CHANGELOG_HEADER: &str = "# Changelog
+
+All notable changes to this project will be documented in this file.
+
+The format is based on [Keep a Changelog](https://keepachangelog.com/en/1.1.0/),
+and this project adheres to [Semantic Versioning](https://semver.org/spec/v2.0.0.html).
+";
+
+const UNRELEASED: &str = "## [Unreleased]";
+
+/// Names of existing changelog files that are picked up, in order of preference.
+/// They are compared case-insensitively, so `CHANGELOG.md` is found as well.
+const FILE_NAMES: &[&str] = &["changelog.md", "changes.md", "history.md"];
+
+/// The changelog file written when a crate has none yet.
+const DEFAULT_FILE: &str = "Changelog.md";
+
+/// The built-in template, the layout of clog's Markdown changelogs.
+const DEFAULT_TEMPLATE: &str = r#"{{#if in_file}}<a name="{{tag}}"></a>
+{{/if}}## {{#if compare_url}}[{{tag}}]({{compare_url}}){{else}}{{tag}}{{/if}} ({{date}})
+
+{{#if breaking_changes}}
+
+#### Breaking Changes
+
+{{#each breaking_changes}}
+*   {{#if scope}}**{{scope}}:**  {{/if}}{{indent description}} ({{#if url}}[{{short_hash}}]({{url}}){{else}}{{short_hash}}{{/if}}{{#each closes}}, closes {{#if url}}[#{{id}}]({{url}}){{else}}#{{id}}{{/if}}{{/each}})
+{{/each}}
+{{/if}}
+{{#each sections}}
+
+#### {{title}}
+
+{{#each commits}}
+*   {{#if scope}}**{{scope}}:**  {{/if}}{{indent description}} ({{#if url}}[{{short_hash}}]({{url}}){{else}}{{short_hash}}{{/if}}{{#each closes}}, closes {{#if url}}[#{{id}}]({{url}}){{else}}#{{id}}{{/if}}{{/each}})
+{{/each}}
+{{/each}}
+{{#if contributors}}
+
+#### Contributors
+
+{{#each contributors}}
+*   {{this}}
+{{/each}}
+{{/if}}
+
+"#;
+
+const KEEP_A_CHANGELOG_TEMPLATE: &str = r#"## [{{version}}] - {{date}}
+{{#each sections}}
+
+### {{title}}
+
+{{#each breaking_changes}}
+- **BREAKING:** {{#if scope}}**{{scope}}:** {{/if}}{{indent description}}
+{{/each}}
+{{#each commits}}
+- {{#if scope}}**{{scope}}:** {{/if}}{{indent description}}{{#if url}} ([{{short_hash}}]({{url}})){{/if}}
+{{/each}}
+{{/each}}
+{{#if contributors}}
+
+### Contributors
+
+{{#each contributors}}
+- {{this}}
+{{/each}}
+{{/if}}
+
+"#;
+
+/// Footer tokens that close issues, in lowercase.
+const CLOSING_TOKENS: &[&str] = &[
+    "close", "closes", "closed", "fix", "fixes", "fixed", "resolve", "resolves", "resolved",
+];
+
+handlebars_helper!(indent: |text: str| text.replace('\n', "\n    "));
+
+/// The layout of the changelog file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    /// New releases on top, the Markdown layout of clog.
+    Clog,
+    /// The layout of keepachangelog.com, with an `[Unreleased]` section
+    /// and link references at the bottom.
+    KeepAChangelog,
 }
 
-pub fn generate(
-    repository_path: &str,
-    old_version: &str,
-    new_version: &str,
+impl FromStr for Style {
+    type Err = String;
+
+    fn from_str(style: &str) -> Result<Style, String> {
+        match style {
+            "clog" => Ok(Style::Clog),
+            "keep-a-changelog" => Ok(Style::KeepAChangelog),
+            _ => Err(format!(
+                "Unknown changelog style '{}', use clog or keep-a-changelog",
+                style
+            )),
+        }
+    }
+}
+
+/// A section of the changelog and the commit types it lists.
+#[derive(Debug, Clone)]
+pub struct SectionSettings {
+    pub title: String,
+    pub types: Vec<String>,
+    /// Whether breaking changes are listed in this section, first.
+    pub breaking: bool,
+}
+
+impl SectionSettings {
+    fn new(title: &str, types: &[&str], breaking: bool) -> SectionSettings {
+        SectionSettings {
+            title: title.to_owned(),
+            types: types.iter().map(|t| t.to_string()).collect(),
+            breaking,
+        }
+    }
+}
+
+/// URL templates of the pages linked from the changelog. `{repository}` is the
+/// web page of the repository, derived from the `origin` remote.
+#[derive(Debug, Clone)]
+pub struct Links {
+    pub repository: Option<String>,
+    /// Page of a commit, with `{hash}`.
+    pub commit: String,
+    /// Changes between two tags, with `{previous_tag}` and `{tag}`.
+    pub compare: String,
+    /// Page of an issue, with `{id}`.
+    pub issue: String,
+}
+
+impl Default for Links {
+    fn default() -> Links {
+        Links {
+            repository: None,
+            commit: "{repository}/commit/{hash}".into(),
+            compare: "{repository}/compare/{previous_tag}...{tag}".into(),
+            issue: "{repository}/issues/{id}".into(),
+        }
+    }
+}
+
+impl Links {
+    /// Fills in a URL template. Without a known repository, templates that
+    /// refer to it give no URL.
+    fn url(&self, template: &str, values: &[(&str, &str)]) -> Option<String> {
+        let mut url = template.to_owned();
+        if url.contains("{repository}") {
+            url = url.replace("{repository}", self.repository.as_ref()?);
+        }
+        for (name, value) in values {
+            url = url.replace(&format!("{{{}}}", name), value);
+        }
+        Some(url)
+    }
+
+    fn commit_url(&self, hash: &str) -> Option<String> {
+        self.url(&self.commit, &[("hash", hash)])
+    }
+
+    fn compare_url(&self, previous_tag: &str, tag: &str) -> Option<String> {
+        self.url(
+            &self.compare,
+            &[("previous_tag", previous_tag), ("tag", tag)],
+        )
+    }
+
+    fn issue_url(&self, id: &str) -> Option<String> {
+        self.url(&self.issue, &[("id", id)])
+    }
+
+    /// Turns references like `#123` into links to the issues.
+    fn link_issues(&self, text: &str) -> String {
+        if self.issue_url("").is_none() {
+            return text.to_owned();
+        }
+
+        let mut linked = String::new();
+        let mut rest = text;
+        while let Some(index) = rest.find('#') {
+            let (before, reference) = rest.split_at(index);
+            linked.push_str(before);
+
+            let digits = reference[1..]
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(reference.len() - 1);
+            let id = &reference[1..1 + digits];
+            let after = &reference[1 + digits..];
+            let standalone = !before.ends_with(|c: char| c.is_alphanumeric() || c == '[')
+                && !after.starts_with(|c: char| c.is_alphanumeric() || c == ']');
+
+            match self.issue_url(id) {
+                Some(url) if !id.is_empty() && standalone => {
+                    linked.push_str(&format!("[#{}]({})", id, url))
+                }
+                _ => linked.push_str(&reference[..1 + digits]),
+            }
+            rest = after;
+        }
+        linked.push_str(rest);
+        linked
+    }
+}
+
+/// How changelogs are rendered.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    style: Style,
+    template: String,
+    /// Sections in the order they are written.
+    sections: Vec<SectionSettings>,
+    /// Commit types left out entirely, even their breaking changes.
+    hidden_types: Vec<String>,
+    pub links: Links,
+    /// Wildcard patterns of names and emails left out of the contributors.
+    pub excluded_contributors: Vec<String>,
+    /// The changelog file, relative to the crate's directory. Found by its
+    /// name if not set.
+    pub path: Option<PathBuf>,
+    /// Whether a changelog file is written at all, rather than only release notes.
+    pub write_file: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings::for_style(Style::Clog)
+    }
+}
+
+impl Settings {
+    /// The built-in template and sections of a style.
+    fn for_style(style: Style) -> Settings {
+        let (template, sections) = match style {
+            Style::Clog => (
+                DEFAULT_TEMPLATE,
+                SECTIONS
+                    .iter()
+                    .map(|&(commit_type, title)| SectionSettings::new(title, &[commit_type], false))
+                    .collect(),
+            ),
+            Style::KeepAChangelog => (
+                KEEP_A_CHANGELOG_TEMPLATE,
+                KEEP_A_CHANGELOG_SECTIONS
+                    .iter()
+                    .map(|&(title, types)| SectionSettings::new(title, types, title == "Changed"))
+                    .collect(),
+            ),
+        };
+
+        Settings {
+            style,
+            template: template.to_owned(),
+            sections,
+            hidden_types: vec![],
+            links: Links::default(),
+            excluded_contributors: vec!["*[bot]".into()],
+            path: None,
+            write_file: true,
+        }
+    }
+
+    /// Replaces the built-in template or sections of the style with configured ones.
+    pub fn new(
+        style: Style,
+        template: Option<String>,
+        sections: Option<Vec<SectionSettings>>,
+        hidden_types: Vec<String>,
+    ) -> Result<Settings, String> {
+        let mut settings = Settings::for_style(style);
+        if let Some(template) = template {
+            settings.template = template;
+        }
+        if let Some(sections) = sections {
+            settings.sections = sections;
+        }
+        settings.hidden_types = hidden_types
+            .iter()
+            .map(|commit_type| commit_type.to_ascii_lowercase())
+            .collect();
+
+        settings.registry()?;
+        Ok(settings)
+    }
+
+    /// The changelog file of a crate in `crate_dir`, relative to it: the
+    /// configured path, an existing file with a common name, or `Changelog.md`.
+    /// None if no changelog file is written.
+    pub fn file(&self, crate_dir: &Path) -> Option<PathBuf> {
+        if !self.write_file {
+            return None;
+        }
+        if let Some(ref path) = self.path {
+            return Some(path.clone());
+        }
+
+        let existing = fs::read_dir(crate_dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_file())
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                let preference = FILE_NAMES
+                    .iter()
+                    .position(|known| name.eq_ignore_ascii_case(known))?;
+                Some((preference, name))
+            })
+            .min();
+
+        Some(PathBuf::from(
+            existing.map_or(DEFAULT_FILE.to_owned(), |(_, name)| name),
+        ))
+    }
+
+    fn registry(&self) -> Result<Handlebars<'static>, String> {
+        let mut registry = Handlebars::new();
+        registry.register_escape_fn(no_escape);
+        registry.register_helper("indent", Box::new(indent));
+        registry
+            .register_template_string("changelog", &self.template)
+            .map_err(|err| format!("Invalid changelog template: {}", err))?;
+        Ok(registry)
+    }
+}
+
+/// A commit that shows up in the changelog.
+struct Entry {
+    hash: String,
+    commit: ConventionalCommit,
+}
+
+/// The commits of a release.
+struct History {
+    /// The Conventional Commits, newest first.
+    entries: Vec<Entry>,
+    /// Everyone who authored or co-authored any of the commits.
+    contributors: Vec<String>,
+}
+
+/// An issue closed by a change.
+#[derive(Debug, Clone, Serialize)]
+struct Issue {
+    id: String,
+    url: Option<String>,
+}
+
+/// A change as seen by the template.
+#[derive(Debug, Clone, Serialize)]
+struct Change {
+    hash: String,
+    short_hash: String,
+    url: Option<String>,
+    #[serde(rename = "type")]
+    commit_type: String,
+    scope: Option<String>,
+    /// The commit's description, or what breaks for a breaking change.
+    description: String,
+    body: Option<String>,
+    breaking: bool,
+    /// Issues closed by `Closes #12` footers and the like.
+    closes: Vec<Issue>,
+}
+
+impl Change {
+    fn new(entry: &Entry, description: &str, links: &Links) -> Change {
+        let closes = entry
+            .commit
+            .footers
+            .iter()
+            .filter(|footer| CLOSING_TOKENS.contains(&footer.token.to_ascii_lowercase().as_str()))
+            .flat_map(|footer| footer.value.split(|c: char| c == ',' || c.is_whitespace()))
+            .map(|id| id.trim_start_matches('#'))
+            .filter(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()))
+            .map(|id| Issue {
+                id: id.to_owned(),
+                url: links.issue_url(id),
+            })
+            .collect();
+
+        Change {
+            hash: entry.hash.clone(),
+            short_hash: entry.hash[..8].to_owned(),
+            url: links.commit_url(&entry.hash),
+            commit_type: entry.commit.commit_type.clone(),
+            scope: entry.commit.scope.clone(),
+            description: links.link_issues(description),
+            body: entry.commit.body.clone(),
+            breaking: entry.commit.breaking,
+            closes,
+        }
+    }
+}
+
+/// The changes of a section with the same scope.
+#[derive(Debug, Serialize)]
+struct ScopeGroup {
+    scope: Option<String>,
+    commits: Vec<Change>,
+}
+
+#[derive(Debug, Serialize)]
+struct Section {
+    title: String,
+    /// Breaking changes, if they are listed in this section.
+    breaking_changes: Vec<Change>,
+    commits: Vec<Change>,
+    /// The same commits, grouped by scope. Unscoped commits come first.
+    scopes: Vec<ScopeGroup>,
+}
+
+impl Section {
+    fn new(title: &str, breaking_changes: Vec<Change>, commits: Vec<Change>) -> Section {
+        let mut scopes: Vec<ScopeGroup> = vec![];
+        for change in &commits {
+            match scopes.iter_mut().find(|group| group.scope == change.scope) {
+                Some(group) => group.commits.push(change.clone()),
+                None => scopes.push(ScopeGroup {
+                    scope: change.scope.clone(),
+                    commits: vec![change.clone()],
+                }),
+            }
+        }
+        scopes.sort_by(|a, b| a.scope.cmp(&b.scope));
+
+        Section {
+            title: title.to_owned(),
+            breaking_changes,
+            commits,
+            scopes,
+        }
+    }
+}
+
+/// Everything a changelog template can use.
+#[derive(Debug, Serialize)]
+struct Context<'a> {
+    version: String,
+    tag: &'a str,
+    /// The tag of the previous release, if there is one.
+    previous_tag: Option<&'a str>,
+    date: &'a str,
+    compare_url: Option<String>,
+    /// Whether the release is written to the changelog file, rather than
+    /// rendered for a tag message or a GitHub release.
+    in_file: bool,
+    breaking_changes: Vec<Change>,
+    sections: Vec<Section>,
+    /// Authors and co-authors, in the order of their first commit.
+    contributors: &'a [String],
+}
+
+/// The commits from `from_tag`, or the start of the history, up to `to` that
+/// pass the filter.
+fn history(
+    repo: &Repository,
+    settings: &Settings,
+    from_tag: Option<&str>,
+    to: &str,
+    filter: &CommitFilter,
+) -> Result<History, String> {
+    if let Some(from_tag) = from_tag {
+        if repo.revparse_single(from_tag).is_err() {
+            warn!("Tag {} not found, the changelog will be empty", from_tag);
+            return Ok(History {
+                entries: vec![],
+                contributors: vec![],
+            });
+        }
+    }
+
+    let commits = git::commits_between(repo, from_tag, to, filter)
+        .map_err(|err| format!("Reading commits up to {} failed: {}", to, err))?;
+
+    let entries = commits
+        .iter()
+        .filter_map(|commit| {
+            let message = commit.message().unwrap_or("");
+            ConventionalCommit::parse(message)
+                .ok()
+                .map(|conventional| Entry {
+                    hash: commit.id().to_string(),
+                    commit: conventional,
+                })
+        })
+        .collect();
+
+    Ok(History {
+        entries,
+        contributors: git::contributors(repo, &commits, &settings.excluded_contributors),
+    })
+}
+
+fn context<'a>(
+    settings: &Settings,
+    version: &Version,
+    tags: (Option<&'a str>, &'a str),
+    date: &'a str,
+    history: &'a History,
+) -> Context<'a> {
+    let (previous_tag, tag) = tags;
+    let entries = history
+        .entries
+        .iter()
+        .filter(|entry| !settings.hidden_types.contains(&entry.commit.commit_type))
+        .collect::<Vec<_>>();
+
+    let breaking_changes = entries
+        .iter()
+        .flat_map(|entry| {
+            entry
+                .commit
+                .breaking_changes()
+                .into_iter()
+                .map(move |change| Change::new(entry, change, &settings.links))
+        })
+        .collect::<Vec<_>>();
+
+    // Breaking changes listed in a section of their own aren't repeated under their type.
+    let lists_breaking = settings.sections.iter().any(|section| section.breaking);
+    let sections = settings
+        .sections
+        .iter()
+        .map(|section| {
+            let commits = entries
+                .iter()
+                .filter(|entry| section.types.contains(&entry.commit.commit_type))
+                .filter(|entry| !(lists_breaking && entry.commit.breaking))
+                .map(|entry| Change::new(entry, &entry.commit.description, &settings.links))
+                .collect();
+            let breaking_changes = if section.breaking {
+                breaking_changes.clone()
+            } else {
+                vec![]
+            };
+            Section::new(&section.title, breaking_changes, commits)
+        })
+        .filter(|section| !section.commits.is_empty() || !section.breaking_changes.is_empty())
+        .collect();
+
+    Context {
+        version: version.to_string(),
+        tag,
+        previous_tag,
+        date,
+        compare_url: previous_tag.and_then(|previous| settings.links.compare_url(previous, tag)),
+        in_file: false,
+        breaking_changes,
+        sections,
+        contributors: &history.contributors,
+    }
+}
+
+/// Renders the changelog section of a release.
+fn render(settings: &Settings, context: &Context) -> Result<String, String> {
+    settings
+        .registry()?
+        .render("changelog", context)
+        .map_err(|err| format!("Rendering the changelog failed: {}", err))
+}
+
+fn section(
+    repo: &Repository,
+    settings: &Settings,
+    release: &Release,
+    filter: &CommitFilter,
+    in_file: bool,
 ) -> Result<String, String> {
-    let mut clog = Clog::with_dir(repository_path).map_err(|_| "Clog failed".to_owned())?;
+    let (from_tag, new_tag) = (release.previous_tag.as_deref(), release.tag_name());
+    let history = history(repo, settings, from_tag, "HEAD", filter)?;
+    let date = Utc::now().format("%Y-%m-%d").to_string();
+
+    let mut context = context(
+        settings,
+        &release.new_version,
+        (from_tag, &new_tag),
+        &date,
+        &history,
+    );
+    context.in_file = in_file;
+    render(settings, &context)
+}
 
-    clog.from(format!("v{}", old_version))
-        .version(format!("v{}", new_version));
+fn is_reference(line: &str) -> bool {
+    line.starts_with('[') && line.contains("]: ")
+}
 
-    let mut out_buf = BufWriter::new(Vec::new());
+/// Link references for a new release in a Keep a Changelog changelog: the
+/// `[Unreleased]` changes since its tag and the changes of the release itself.
+/// The first release links to its tagged commit.
+fn reference_links(
+    settings: &Settings,
+    version: &Version,
+    previous_tag: Option<&str>,
+    tag: &str,
+) -> Vec<String> {
+    let unreleased = settings.links.compare_url(tag, "HEAD");
+    let released = match previous_tag {
+        Some(previous_tag) => settings.links.compare_url(previous_tag, tag),
+        None => settings.links.commit_url(tag),
+    };
 
+    match (unreleased, released) {
+        (Some(unreleased), Some(released)) => vec![
+            format!("[Unreleased]: {}", unreleased),
+            format!("[{}]: {}", version, released),
+        ],
+        _ => vec![],
+    }
+}
+
+/// Inserts a release below the `[Unreleased]` section of a Keep a Changelog
+/// changelog, and its link references above those of older releases.
+fn insert_release(existing: &str, section: &str, links: &[String]) -> String {
+    let existing = if existing.trim().is_empty() {
+        format!("{}\n{}\n", KEEP_A_CHANGELOG_HEADER, UNRELEASED)
+    } else {
+        existing.to_owned()
+    };
+    let lines = existing.lines().collect::<Vec<_>>();
+
+    let mut body_end = lines.len();
+    while body_end > 0
+        && (lines[body_end - 1].trim().is_empty() || is_reference(lines[body_end - 1]))
     {
-        let mut writer = MarkdownWriter::new(&mut out_buf);
-        clog.write_changelog_with(&mut writer)
-            .map_err(|_| "Genearting changelog failed")?
+        body_end -= 1;
+    }
+    let (body, references) = lines.split_at(body_end);
+
+    let is_release = |line: &&str| line.starts_with("## ");
+    let (mut before, after) = match body.iter().position(|line| line.trim() == UNRELEASED) {
+        Some(unreleased) => {
+            let end = body[unreleased + 1..]
+                .iter()
+                .position(is_release)
+                .map_or(body.len(), |offset| unreleased + 1 + offset);
+            (body[..end].join("\n"), &body[end..])
+        }
+        None => {
+            let start = body.iter().position(is_release).unwrap_or(body.len());
+            let before = body[..start].join("\n");
+            (
+                format!("{}\n\n{}", before.trim_end(), UNRELEASED),
+                &body[start..],
+            )
+        }
+    };
+
+    before = before.trim_end().to_owned() + "\n\n";
+    let mut changelog = before + section;
+    if !after.is_empty() {
+        changelog.push_str(&after.join("\n"));
+        changelog.push('\n');
+    }
+
+    let new_labels = links
+        .iter()
+        .filter_map(|link| link.split("]: ").next())
+        .collect::<Vec<_>>();
+    let references = links
+        .iter()
+        .map(String::as_str)
+        .chain(references.iter().cloned().filter(|line| {
+            is_reference(line) && !new_labels.iter().any(|label| line.starts_with(label))
+        }))
+        .collect::<Vec<_>>();
+    if !references.is_empty() {
+        changelog = changelog.trim_end().to_owned() + "\n\n" + &references.join("\n") + "\n";
+    }
+
+    changelog
+}
+
+/// Adds a release section to a changelog, in the place of the style.
+fn add_release(
+    settings: &Settings,
+    existing: &str,
+    section: String,
+    version: &Version,
+    tags: (Option<&str>, &str),
+) -> String {
+    let (previous_tag, tag) = tags;
+    match settings.style {
+        Style::Clog => section + existing,
+        Style::KeepAChangelog => insert_release(
+            existing,
+            &section,
+            &reference_links(settings, version, previous_tag, tag),
+        ),
+    }
+}
+
+/// Formats the time of a tag as a date in the tagger's time zone.
+fn tag_date(time: git2::Time) -> String {
+    FixedOffset::east_opt(time.offset_minutes() * 60)
+        .and_then(|offset| offset.timestamp_opt(time.seconds(), 0).single())
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
+}
+
+/// Adds the changes of a release to the changelog file.
+pub fn write(
+    repo: &Repository,
+    settings: &Settings,
+    changelog_file: &Path,
+    release: &Release,
+    filter: &CommitFilter,
+) -> Result<(), String> {
+    let existing = match fs::read_to_string(changelog_file) {
+        Ok(existing) => existing,
+        Err(ref err) if err.kind() == ErrorKind::NotFound => String::new(),
+        Err(_) => return Err(format!("Failed to read {}", changelog_file.display())),
+    };
+
+    let section = section(repo, settings, release, filter, true)?;
+    let changelog = add_release(
+        settings,
+        &existing,
+        section,
+        &release.new_version,
+        (release.previous_tag.as_deref(), &release.tag_name()),
+    );
+    replace(changelog_file, &changelog)
+}
+
+/// Rebuilds the whole changelog of a crate from its release tags, oldest to
+/// newest. The first release lists the history up to its tag and every
+/// release is dated by its tag, so the result only changes with new tags.
+pub fn regenerate(
+    repo: &Repository,
+    settings: &Settings,
+    package: &Package,
+    filter: &CommitFilter,
+) -> Result<String, String> {
+    let mut tags = git::tagged_versions(repo, &package.tag_format, &package.name);
+    tags.sort();
+
+    let mut changelog = String::new();
+    let mut previous: Option<(Version, String)> = None;
+    for (version, tag) in tags {
+        let previous_tag = previous.as_ref().map(|(_, tag)| tag.as_str());
+        let history = history(repo, settings, previous_tag, &tag, filter)?;
+        let date = git::tag_time(repo, &tag).map(tag_date).unwrap_or_default();
+
+        let mut context = context(settings, &version, (previous_tag, &tag), &date, &history);
+        context.in_file = true;
+        let section = render(settings, &context)?;
+        changelog = add_release(
+            settings,
+            &changelog,
+            section,
+            &version,
+            (previous_tag, &tag),
+        );
+
+        previous = Some((version, tag));
+    }
+
+    Ok(changelog)
+}
+
+/// Replaces the contents of the changelog file, creating its directory if needed.
+pub fn replace(changelog_file: &Path, changelog: &str) -> Result<(), String> {
+    if let Some(directory) = changelog_file.parent() {
+        fs::create_dir_all(directory)
+            .map_err(|_| format!("Failed to create {}", directory.display()))?;
+    }
+    fs::write(changelog_file, changelog)
+        .map_err(|_| format!("Failed to write {}", changelog_file.display()))
+}
+
+/// Renders the changes of a release for a tag message or a GitHub release.
+pub fn generate(
+    repo: &Repository,
+    settings: &Settings,
+    release: &Release,
+    filter: &CommitFilter,
+) -> Result<String, String> {
+    section(repo, settings, release, filter, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    use crate::commit_analyzer::CommitType;
+    use crate::tag_format::TagFormat;
+    use crate::test_repo::TempRepo;
+    use crate::toml_file::VersionLocation;
+    use crate::workspace::Package;
+
+    fn entry(hash: &str, message: &str) -> Entry {
+        Entry {
+            hash: hash.repeat(40),
+            commit: ConventionalCommit::parse(message).unwrap(),
+        }
+    }
+
+    fn entries() -> Vec<Entry> {
+        vec![
+            entry("a", "feat(parser): Math mode"),
+            entry("b", "fix: Into the void"),
+            entry("c", "docs: Not in the changelog"),
+            entry("d", "feat!: Drop the old API"),
+            entry("e", "remove: The unused cache"),
+        ]
+    }
+
+    fn release() -> Release {
+        Release {
+            package: Package {
+                name: "foo".into(),
+                path: PathBuf::new(),
+                nested_paths: vec![],
+                workspace_member: false,
+                tag_format: TagFormat::new("v{version}").unwrap(),
+                version_location: VersionLocation::Package(PathBuf::new()),
+            },
+            version: Version::parse("1.2.0").unwrap(),
+            new_version: Version::parse("2.0.0").unwrap(),
+            previous_tag: Some("v1.2.0".into()),
+            bump: CommitType::Major,
+        }
+    }
+
+    fn render_with(settings: &Settings, in_file: bool) -> String {
+        render_with_contributors(settings, in_file, &[])
+    }
+
+    fn render_with_contributors(
+        settings: &Settings,
+        in_file: bool,
+        contributors: &[&str],
+    ) -> String {
+        let history = History {
+            entries: entries(),
+            contributors: contributors.iter().map(|name| name.to_string()).collect(),
+        };
+        let mut context = context(
+            settings,
+            &release().new_version,
+            (Some("v1.2.0"), "v2.0.0"),
+            "2016-07-03",
+            &history,
+        );
+        context.in_file = in_file;
+        render(settings, &context).unwrap()
+    }
+
+    #[test]
+    fn renders_sections() {
+        assert_eq!(
+            render_with(&Settings::default(), true),
+            "<a name=\"v2.0.0\"></a>
+## v2.0.0 (2016-07-03)
+
+
+#### Breaking Changes
+
+*   Drop the old API (dddddddd)
+
+#### Features
+
+*   **parser:**  Math mode (aaaaaaaa)
+*   Drop the old API (dddddddd)
+
+#### Bug Fixes
+
+*   Into the void (bbbbbbbb)
+
+"
+        );
+        assert!(render_with(&Settings::default(), false).starts_with("## v2.0.0 (2016-07-03)\n"));
+    }
+
+    #[test]
+    fn renders_configured_sections() {
+        let sections = vec![
+            SectionSettings::new("Fixed", &["fix"], false),
+            SectionSettings::new("Documentation", &["docs"], false),
+        ];
+        let settings =
+            Settings::new(Style::Clog, None, Some(sections), vec!["feat".into()]).unwrap();
+
+        assert_eq!(
+            render_with(&settings, false),
+            "## v2.0.0 (2016-07-03)
+
+
+#### Fixed
+
+*   Into the void (bbbbbbbb)
+
+#### Documentation
+
+*   Not in the changelog (cccccccc)
+
+"
+        );
+    }
+
+    #[test]
+    fn renders_custom_templates() {
+        let template = "{{version}} {{compare_url}}
+{{#each sections}}{{title}}:{{#each scopes}} {{#if scope}}{{scope}}{{else}}-{{/if}}={{#each commits}}{{short_hash}}{{/each}}{{/each}}
+{{/each}}{{#each contributors}}{{this}};{{/each}}";
+        let mut settings = Settings::new(Style::Clog, Some(template.into()), None, vec![]).unwrap();
+        settings.links.repository = Some("https://github.com/foo/bar".into());
+
+        assert_eq!(
+            render_with_contributors(
+                &settings,
+                false,
+                &["Author d", "Author c", "Author b", "Author a"]
+            ),
+            "2.0.0 https://github.com/foo/bar/compare/v1.2.0...v2.0.0
+Features: -=dddddddd parser=aaaaaaaa
+Bug Fixes: -=bbbbbbbb
+Author d;Author c;Author b;Author a;"
+        );
+
+        assert!(Settings::new(Style::Clog, Some("{{#each}}".into()), None, vec![]).is_err());
+    }
+
+    #[test]
+    fn renders_keep_a_changelog() {
+        let settings = Settings::for_style(Style::KeepAChangelog);
+        assert_eq!(
+            render_with(&settings, true),
+            "## [2.0.0] - 2016-07-03
+
+### Added
+
+- **parser:** Math mode
+
+### Changed
+
+- **BREAKING:** Drop the old API
+
+### Removed
+
+- The unused cache
+
+### Fixed
+
+- Into the void
+
+"
+        );
+    }
+
+    #[test]
+    fn renders_contributors() {
+        let contributors = ["Jane Doe", "Max Mustermann"];
+        assert!(
+            render_with_contributors(&Settings::default(), false, &contributors).ends_with(
+                "*   Into the void (bbbbbbbb)
+
+#### Contributors
+
+*   Jane Doe
+*   Max Mustermann
+
+"
+            )
+        );
+
+        let settings = Settings::for_style(Style::KeepAChangelog);
+        assert!(
+            render_with_contributors(&settings, true, &contributors).ends_with(
+                "- Into the void
+
+### Contributors
+
+- Jane Doe
+- Max Mustermann
+
+"
+            )
+        );
+    }
+
+    #[test]
+    fn inserts_keep_a_changelog_releases() {
+        let section = "## [1.0.0] - 2016-07-03\n\n### Fixed\n\n- Into the void\n\n";
+        let links = vec![
+            "[Unreleased]: https://github.com/foo/bar/compare/v1.0.0...HEAD".to_string(),
+            "[1.0.0]: https://github.com/foo/bar/compare/v0.9.0...v1.0.0".to_string(),
+        ];
+        let changelog = insert_release("", section, &links);
+        assert_eq!(
+            changelog,
+            format!(
+                "{}\n## [Unreleased]\n\n{}{}\n{}\n",
+                KEEP_A_CHANGELOG_HEADER, section, links[0], links[1]
+            )
+        );
+
+        let section = "## [1.1.0] - 2016-08-01\n\n### Added\n\n- Math mode\n\n";
+        let links = vec![
+            "[Unreleased]: https://github.com/foo/bar/compare/v1.1.0...HEAD".to_string(),
+            "[1.1.0]: https://github.com/foo/bar/compare/v1.0.0...v1.1.0".to_string(),
+        ];
+        let changelog = insert_release(&changelog, section, &links);
+        assert!(changelog.ends_with(
+            "## [Unreleased]
+
+## [1.1.0] - 2016-08-01
+
+### Added
+
+- Math mode
+
+## [1.0.0] - 2016-07-03
+
+### Fixed
+
+- Into the void
+
+[Unreleased]: https://github.com/foo/bar/compare/v1.1.0...HEAD
+[1.1.0]: https://github.com/foo/bar/compare/v1.0.0...v1.1.0
+[1.0.0]: https://github.com/foo/bar/compare/v0.9.0...v1.0.0
+"
+        ));
+    }
+
+    #[test]
+    fn finds_changelog_files() {
+        let repo = TempRepo::new();
+        let path = repo.path();
+        fs::create_dir_all(path.join("changelog.md")).unwrap();
+        let mut settings = Settings::default();
+        assert_eq!(settings.file(path), Some(PathBuf::from("Changelog.md")));
+
+        fs::write(path.join("HISTORY.md"), "").unwrap();
+        assert_eq!(settings.file(path), Some(PathBuf::from("HISTORY.md")));
+        fs::write(path.join("CHANGES.md"), "").unwrap();
+        assert_eq!(settings.file(path), Some(PathBuf::from("CHANGES.md")));
+
+        settings.path = Some(PathBuf::from("docs/NEWS.md"));
+        assert_eq!(settings.file(path), Some(PathBuf::from("docs/NEWS.md")));
+        settings.write_file = false;
+        assert_eq!(settings.file(path), None);
+    }
+
+    #[test]
+    fn writes_first_release() {
+        let repo = TempRepo::new();
+        repo.commit("feat: Math mode").create();
+        repo.commit("fix: Into the void").create();
+
+        let mut release = release();
+        release.version = Version::parse("0.0.0").unwrap();
+        release.new_version = Version::parse("0.1.0").unwrap();
+        release.previous_tag = None;
+        let mut settings = Settings::for_style(Style::KeepAChangelog);
+        settings.links.repository = Some("https://github.com/foo/bar".into());
+
+        write(
+            &repo,
+            &settings,
+            &repo.path().join("Changelog.md"),
+            &release,
+            &CommitFilter::default(),
+        )
+        .unwrap();
+        let changelog = fs::read_to_string(repo.path().join("Changelog.md")).unwrap();
+        assert!(changelog.contains("### Added\n\n- Math mode"));
+        assert!(changelog.contains("### Fixed\n\n- Into the void"));
+        assert!(changelog.ends_with(
+            "[Unreleased]: https://github.com/foo/bar/compare/v0.1.0...HEAD
+[0.1.0]: https://github.com/foo/bar/commit/v0.1.0
+"
+        ));
+    }
+
+    #[test]
+    fn regenerates_from_tags() {
+        let repo = TempRepo::new();
+        for (day, message, tag) in &[
+            (1, "feat: Math mode", Some("v0.1.0")),
+            (2, "fix: Into the void", None),
+            (3, "feat!: Drop the old API", Some("v1.0.0")),
+            (4, "fix: Not released", None),
+        ] {
+            let oid = repo
+                .commit(message)
+                .time(1_467_504_000 + day * 86_400)
+                .create();
+            if let Some(tag) = tag {
+                repo.tag_commit(oid, tag);
+            }
+        }
+
+        let package = release().package;
+        let mut settings = Settings::for_style(Style::KeepAChangelog);
+        settings.links.repository = Some("https://github.com/foo/bar".into());
+        let changelog = regenerate(&repo, &settings, &package, &CommitFilter::default()).unwrap();
+
+        assert!(changelog.starts_with(KEEP_A_CHANGELOG_HEADER));
+        let headings = changelog
+            .lines()
+            .filter(|line| line.starts_with("## ") || line.starts_with("- **"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            headings,
+            vec![
+                "## [Unreleased]",
+                "## [1.0.0] - 2016-07-06",
+                "- **BREAKING:** Drop the old API",
+                "## [0.1.0] - 2016-07-04",
+            ]
+        );
+        assert!(changelog.ends_with(
+            "[Unreleased]: https://github.com/foo/bar/compare/v1.0.0...HEAD
+[1.0.0]: https://github.com/foo/bar/compare/v0.1.0...v1.0.0
+[0.1.0]: https://github.com/foo/bar/commit/v0.1.0
+"
+        ));
+        assert_eq!(
+            regenerate(&repo, &settings, &package, &CommitFilter::default()).unwrap(),
+            changelog
+        );
+
+        let changelog = regenerate(
+            &repo,
+            &Settings::default(),
+            &package,
+            &CommitFilter::default(),
+        )
+        .unwrap();
+        let v1 = changelog.find("## v1.0.0 (2016-07-06)").unwrap();
+        let v0 = changelog.find("## v0.1.0 (2016-07-04)").unwrap();
+        assert!(v1 < v0);
+        assert!(!changelog.contains("Not released"));
+    }
+
+    #[test]
+    fn links_commits_and_issues() {
+        let mut settings = Settings::default();
+        settings.links.repository = Some("https://github.com/foo/bar".into());
+        let entry = entry(
+            "a",
+            "fix: Handle #12 and C#1, not [#3](x)\n\nCloses #4\nFixes: #5, #6",
+        );
+
+        let change = Change::new(&entry, &entry.commit.description, &settings.links);
+        assert_eq!(
+            change.url.as_deref(),
+            Some("https://github.com/foo/bar/commit/aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa")
+        );
+        assert_eq!(
+            change.description,
+            "Handle [#12](https://github.com/foo/bar/issues/12) and C#1, not [#3](x)"
+        );
+        let closes = change
+            .closes
+            .iter()
+            .map(|issue| &issue.id[..])
+            .collect::<Vec<_>>();
+        assert_eq!(closes, vec!["4", "5", "6"]);
+
+        settings.links.repository = None;
+        settings.links.issue = "https://tracker.example.com/browse/{id}".into();
+        let change = Change::new(&entry, &entry.commit.description, &settings.links);
+        assert_eq!(change.url, None);
+        assert_eq!(
+            change.closes[0].url.as_deref(),
+            Some("https://tracker.example.com/browse/4")
+        );
+    }
+
+    #[test]
+    fn renders_plain_hashes_without_a_remote() {
+        let settings = Settings::default();
+        assert_eq!(settings.links.repository, None);
+
+        let rendered = render_with(&settings, false);
+        assert!(rendered.contains("*   Into the void (bbbbbbbb)\n"));
+        assert!(!rendered.contains("]("));
     }
 
-    let out_buf = out_buf.into_inner().unwrap();
-    let changelog = String::from_utf8(out_buf).unwrap();
+    #[test]
+    fn renders_links() {
+        let mut settings = Settings::default();
+        settings.links.repository = Some("https://git.example.com/foo/bar".into());
+        settings.links.commit = "{repository}/-/commit/{hash}".into();
 
-    match changelog.find('\n') {
-        Some(newline_offset) => Ok(changelog[newline_offset + 1..].into()),
-        None => Ok(changelog),
+        let rendered = render_with(&settings, false);
+        assert!(rendered.starts_with(
+            "## [v2.0.0](https://git.example.com/foo/bar/compare/v1.2.0...v2.0.0) (2016-07-03)"
+        ));
+        assert!(rendered.contains(&format!(
+            "*   Into the void ([bbbbbbbb](https://git.example.com/foo/bar/-/commit/{}))",
+            "b".repeat(40)
+        )));
     }
 }
@@ -1,7 +1,12 @@
-use clog::error::Error;
-use clog::Clog;
+use std::collections::BTreeMap;
+use std::str::FromStr;
 
-#[derive(PartialEq, Eq, Debug, PartialOrd, Ord)]
+use semver::Version;
+use serde::{Serialize, Serializer};
+
+use crate::conventional_commit::ConventionalCommit;
+
+#[derive(PartialEq, Eq, Debug, PartialOrd, Ord, Clone, Copy)]
 pub enum CommitType {
     Unknown,
     Patch,
@@ -11,43 +16,321 @@ pub enum CommitType {
 
 use self::CommitType::*;
 
-pub fn analyze_single(commit: &str) -> Result<CommitType, Error> {
-    let clog = Clog::new().expect("Clog initialization failed");
-    let commit = clog.parse_raw_commit(commit);
+impl FromStr for CommitType {
+    type Err = String;
+
+    /// Parses a bump level: `major`, `minor`, `patch` or `none`.
+    fn from_str(level: &str) -> Result<CommitType, String> {
+        match level {
+            "major" => Ok(Major),
+            "minor" => Ok(Minor),
+            "patch" => Ok(Patch),
+            "none" => Ok(Unknown),
+            _ => Err(format!(
+                "'{}' is not a bump level, use major, minor, patch or none",
+                level
+            )),
+        }
+    }
+}
+
+impl CommitType {
+    /// The name of the bump level, as accepted by `from_str`.
+    pub fn level(self) -> &'static str {
+        match self {
+            Major => "major",
+            Minor => "minor",
+            Patch => "patch",
+            Unknown => "none",
+        }
+    }
+}
+
+impl Serialize for CommitType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.level())
+    }
+}
+
+/// Which bump a commit calls for, by its type or its type and scope.
+///
+/// Breaking changes always call for a major bump.
+#[derive(Debug, Clone)]
+pub struct BumpRules {
+    /// Levels by `type` or `type(scope)`.
+    levels: BTreeMap<String, CommitType>,
+    /// Level of every other commit, including those that are no Conventional Commits.
+    default: CommitType,
+}
+
+impl Default for BumpRules {
+    fn default() -> BumpRules {
+        let levels = vec![("feat".to_string(), Minor), ("fix".to_string(), Patch)];
+        BumpRules {
+            levels: levels.into_iter().collect(),
+            default: Unknown,
+        }
+    }
+}
+
+impl BumpRules {
+    /// Adds configured levels, like `perf = "patch"` or `"fix(internal)" = "none"`,
+    /// to the default rules.
+    pub fn new(
+        levels: &BTreeMap<String, String>,
+        default: Option<&str>,
+    ) -> Result<BumpRules, String> {
+        let mut rules = BumpRules::default();
+        for (commit_type, level) in levels {
+            rules
+                .levels
+                .insert(commit_type.to_ascii_lowercase(), level.parse()?);
+        }
+        if let Some(default) = default {
+            rules.default = default.parse()?;
+        }
+        Ok(rules)
+    }
+
+    pub fn analyze(&self, commit: &ConventionalCommit) -> CommitType {
+        if commit.breaking {
+            return Major;
+        }
+
+        let scoped = commit.scope.as_ref().and_then(|scope| {
+            self.levels
+                .get(&format!("{}({})", commit.commit_type, scope))
+        });
+        scoped
+            .or_else(|| self.levels.get(&commit.commit_type))
+            .cloned()
+            .unwrap_or(self.default)
+    }
+}
+
+/// Determines the bump a commit message calls for. Messages that are not
+/// Conventional Commits get the default level.
+#[cfg(test)]
+fn analyze_single(message: &str, rules: &BumpRules) -> CommitType {
+    CommitRecord::new(String::new(), message, rules).bump
+}
+
+/// How a commit of the analyzed range was classified.
+#[derive(Debug, Serialize)]
+pub struct CommitRecord {
+    pub sha: String,
+    pub subject: String,
+    #[serde(rename = "type")]
+    pub commit_type: Option<String>,
+    pub scope: Option<String>,
+    pub breaking: bool,
+    pub bump: CommitType,
+    /// Why the message is no Conventional Commit and got the default level.
+    pub parse_error: Option<String>,
+    /// Why the commit does not count towards the release.
+    pub skipped: Option<String>,
+}
+
+impl CommitRecord {
+    pub fn new(sha: String, message: &str, rules: &BumpRules) -> CommitRecord {
+        let subject = message.lines().next().unwrap_or("").trim().to_owned();
+        match ConventionalCommit::parse(message) {
+            Ok(commit) => CommitRecord {
+                sha,
+                subject,
+                bump: rules.analyze(&commit),
+                commit_type: Some(commit.commit_type),
+                scope: commit.scope,
+                breaking: commit.breaking,
+                parse_error: None,
+                skipped: None,
+            },
+            Err(error) => CommitRecord {
+                sha,
+                subject,
+                commit_type: None,
+                scope: None,
+                breaking: false,
+                bump: rules.default,
+                parse_error: Some(error.to_string()),
+                skipped: None,
+            },
+        }
+    }
+
+    /// The bump this commit contributes to the release.
+    pub fn effective_bump(&self) -> CommitType {
+        match self.skipped {
+            Some(_) => Unknown,
+            None => self.bump,
+        }
+    }
+}
+
+/// The version requested with a `Release-As: 1.2.3` footer, if any.
+pub fn release_as(message: &str) -> Option<Version> {
+    ConventionalCommit::parse(message)
+        .ok()?
+        .footer("Release-As")
+        .and_then(|version| Version::parse(version).ok())
+}
+
+/// Whether a commit message asks not to be released, with `[skip release]`
+/// or `[release skip]`.
+pub fn skips_release(message: &str) -> bool {
+    let message = message.to_ascii_lowercase();
+    message.contains("[skip release]") || message.contains("[release skip]")
+}
+
+fn is_hash(word: &str) -> bool {
+    word.len() >= 7 && word.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Commits a revert undoes, by their possibly abbreviated hashes: from git's
+/// `This reverts commit <hash>.` line, or the `Refs` footer of a `revert:` commit.
+pub fn reverted_hashes(message: &str) -> Vec<String> {
+    let mut hashes = message
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("This reverts commit "))
+        .filter_map(|rest| rest.split(|c: char| !c.is_ascii_hexdigit()).next())
+        .filter(|hash| is_hash(hash))
+        .map(str::to_ascii_lowercase)
+        .collect::<Vec<_>>();
 
-    if !commit.breaks.is_empty() {
-        return Ok(Major);
+    if let Ok(commit) = ConventionalCommit::parse(message) {
+        if commit.commit_type == "revert" {
+            let refs = commit.footer("Refs").unwrap_or("");
+            hashes.extend(
+                refs.split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|hash| is_hash(hash))
+                    .map(str::to_ascii_lowercase),
+            );
+        }
     }
 
-    let commit_type = match &commit.commit_type[..] {
-        "Features" => Minor,
-        "Bug Fixes" => Patch,
-        _ => Unknown,
-    };
+    hashes
+}
 
-    Ok(commit_type)
+/// The header of the commit undone by a revert with git's `Revert "<header>"` message.
+pub fn reverted_header(message: &str) -> Option<&str> {
+    message
+        .lines()
+        .next()?
+        .strip_prefix("Revert \"")?
+        .strip_suffix('"')
 }
 
 #[test]
 fn unknown_type() {
-    let commit = "0\nThis commit message has no type";
-    assert_eq!(Unknown, analyze_single(commit).unwrap());
+    let commit = "This commit message has no type";
+    assert_eq!(Unknown, analyze_single(commit, &BumpRules::default()));
 }
 
 #[test]
 fn patch_commit() {
-    let commit = "0\nfix: This commit fixes a bug";
-    assert_eq!(Patch, analyze_single(commit).unwrap());
+    let commit = "fix: This commit fixes a bug";
+    assert_eq!(Patch, analyze_single(commit, &BumpRules::default()));
 }
 
 #[test]
 fn minor_commit() {
-    let commit = "0\nfeat: This commit introduces a new feature";
-    assert_eq!(Minor, analyze_single(commit).unwrap());
+    let commit = "feat: This commit introduces a new feature";
+    assert_eq!(Minor, analyze_single(commit, &BumpRules::default()));
 }
 
 #[test]
 fn major_commit() {
-    let commit = "0\nfeat: This commits breaks something\nBREAKING CHANGE: breaks things";
-    assert_eq!(Major, analyze_single(commit).unwrap());
+    let commit = "feat: This commits breaks something\nBREAKING CHANGE: breaks things";
+    assert_eq!(Major, analyze_single(commit, &BumpRules::default()));
+}
+
+#[test]
+fn breaking_commits() {
+    let rules = BumpRules::default();
+    assert_eq!(Major, analyze_single("feat!: Drop the old API", &rules));
+    assert_eq!(
+        Major,
+        analyze_single("fix(parser)!: Reject invalid input", &rules)
+    );
+    assert_eq!(
+        Major,
+        analyze_single(
+            "chore: Bump MSRV\n\nBREAKING-CHANGE: needs Rust 1.60",
+            &rules
+        )
+    );
+}
+
+#[test]
+fn configured_levels() {
+    let levels = vec![
+        ("perf", "patch"),
+        ("refactor", "none"),
+        ("fix(internal)", "none"),
+    ]
+    .into_iter()
+    .map(|(commit_type, level)| (commit_type.to_string(), level.to_string()))
+    .collect();
+    let rules = BumpRules::new(&levels, Some("patch")).unwrap();
+
+    assert_eq!(Patch, analyze_single("perf: Cache results", &rules));
+    assert_eq!(Unknown, analyze_single("refactor: Split module", &rules));
+    assert_eq!(Unknown, analyze_single("fix(internal): Rename", &rules));
+    assert_eq!(Patch, analyze_single("fix(api): Validate input", &rules));
+    assert_eq!(Minor, analyze_single("feat: New command", &rules));
+    assert_eq!(Patch, analyze_single("Update README", &rules));
+    assert_eq!(Major, analyze_single("fix(internal)!: Remove API", &rules));
+
+    let levels = vec![("perf".to_string(), "tiny".to_string())]
+        .into_iter()
+        .collect();
+    assert!(BumpRules::new(&levels, None).is_err());
+}
+
+#[test]
+fn release_as_footer() {
+    let message = "chore: Cut the first stable release\n\nRelease-As: 1.0.0";
+    assert_eq!(release_as(message), Some(Version::parse("1.0.0").unwrap()));
+    assert_eq!(release_as("fix: Release-As is not a footer here"), None);
+    assert_eq!(release_as("chore: nothing\n\nrelease-as: v2"), None);
+}
+
+#[test]
+fn reverts() {
+    let message = "Revert \"feat: Add X\"\n\nThis reverts commit 0a1b2c3d4e5f.";
+    assert_eq!(reverted_hashes(message), vec!["0a1b2c3d4e5f"]);
+    assert_eq!(reverted_header(message), Some("feat: Add X"));
+
+    let message = "revert: Never speak of the noodle incident\n\nRefs: 676104e, A215868";
+    assert_eq!(reverted_hashes(message), vec!["676104e", "a215868"]);
+    assert_eq!(reverted_header(message), None);
+
+    assert!(reverted_hashes("fix: Refs are no reverts\n\nRefs: 676104e").is_empty());
+}
+
+#[test]
+fn commit_records() {
+    let rules = BumpRules::default();
+    let record = CommitRecord::new("abc".into(), "feat(cli)!: Drop --verbose\n\nBody", &rules);
+    assert_eq!(record.subject, "feat(cli)!: Drop --verbose");
+    assert_eq!(record.commit_type.as_deref(), Some("feat"));
+    assert_eq!(record.scope.as_deref(), Some("cli"));
+    assert!(record.breaking);
+    assert_eq!(record.bump, Major);
+    assert_eq!(record.parse_error, None);
+
+    let record = CommitRecord::new("def".into(), "Update README", &rules);
+    assert_eq!(record.commit_type, None);
+    assert_eq!(record.bump, Unknown);
+    assert_eq!(
+        record.parse_error.as_deref(),
+        Some("the header has no type, like `fix: ...`")
+    );
+}
+
+#[test]
+fn skip_release_markers() {
+    assert!(skips_release("docs: Fix typo [skip release]"));
+    assert!(skips_release("fix: Quick fix\n\n[Release Skip]"));
+    assert!(!skips_release("fix: Skip release notes for drafts"));
 }
@@ -1,10 +1,23 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
 use git2::{Repository, Signature};
+use semver::Version;
+
+use crate::changelog;
+use crate::commit_analyzer::{BumpRules, CommitType};
+use crate::config_file::BranchConfig;
+use crate::tag_format::TagFormat;
+use crate::version::InitialVersion;
+use crate::version_file::VersionFile;
 
 pub struct Config {
     pub user: Option<String>,
     pub repository_name: Option<String>,
 
-    pub branch: String,
+    /// The checked out branch, None on a detached HEAD when explaining.
+    pub branch: Option<String>,
+    pub branches: BTreeMap<String, BranchConfig>,
 
     pub remote: Result<String, String>,
 
@@ -14,11 +27,29 @@ pub struct Config {
     pub release_mode: bool,
 
     pub repository: Repository,
-    pub signature: Signature<'static>,
+    /// The committer, only looked up in write mode.
+    pub signature: Option<Signature<'static>>,
 
     pub gh_username: Option<String>,
     pub gh_token: Option<String>,
     pub cargo_token: Option<String>,
+
+    pub shared_paths: Vec<PathBuf>,
+    pub ignored_authors: Vec<String>,
+    pub ignored_paths: Vec<PathBuf>,
+    pub tag_format: Option<TagFormat>,
+    pub first_parent: bool,
+
+    pub bump: Option<CommitType>,
+    pub release_as: Option<Version>,
+    /// The crate `bump` and `release_as` apply to, needed in a workspace.
+    pub bump_package: Option<String>,
+    pub initial_version: Option<InitialVersion>,
+    pub bump_rules: BumpRules,
+    pub changelog: changelog::Settings,
+    /// Where the JSON release manifest is written, relative to the repository root.
+    pub manifest: Option<PathBuf>,
+    pub version_files: Vec<VersionFile>,
 }
 
 impl Config {
@@ -40,6 +71,7 @@ pub struct ConfigBuilder {
     repository_name: Option<String>,
 
     branch: Option<String>,
+    branches: BTreeMap<String, BranchConfig>,
 
     repository_path: Option<String>,
 
@@ -54,6 +86,21 @@ pub struct ConfigBuilder {
     gh_username: Option<String>,
     gh_token: Option<String>,
     cargo_token: Option<String>,
+
+    shared_paths: Vec<PathBuf>,
+    ignored_authors: Vec<String>,
+    ignored_paths: Vec<PathBuf>,
+    tag_format: Option<TagFormat>,
+    first_parent: bool,
+
+    bump: Option<CommitType>,
+    release_as: Option<Version>,
+    bump_package: Option<String>,
+    initial_version: Option<InitialVersion>,
+    bump_rules: BumpRules,
+    changelog: changelog::Settings,
+    manifest: Option<PathBuf>,
+    version_files: Vec<VersionFile>,
 }
 
 impl ConfigBuilder {
@@ -62,6 +109,7 @@ impl ConfigBuilder {
             user: None,
             repository_name: None,
             branch: None,
+            branches: BTreeMap::new(),
             repository_path: None,
             write_mode: false,
             release_mode: false,
@@ -71,6 +119,19 @@ impl ConfigBuilder {
             gh_token: None,
             cargo_token: None,
             remote: None,
+            shared_paths: vec![],
+            ignored_authors: vec![],
+            ignored_paths: vec![],
+            tag_format: None,
+            first_parent: false,
+            bump: None,
+            release_as: None,
+            bump_package: None,
+            initial_version: None,
+            bump_rules: BumpRules::default(),
+            changelog: changelog::Settings::default(),
+            manifest: None,
+            version_files: vec![],
         }
     }
 
@@ -89,6 +150,11 @@ impl ConfigBuilder {
         self
     }
 
+    pub fn branches(&mut self, branches: BTreeMap<String, BranchConfig>) -> &mut Self {
+        self.branches = branches;
+        self
+    }
+
     pub fn repository_path(&mut self, path: String) -> &mut Self {
         self.repository_path = Some(path);
         self
@@ -134,20 +200,99 @@ impl ConfigBuilder {
         self
     }
 
+    pub fn shared_paths(&mut self, paths: Vec<PathBuf>) -> &mut Self {
+        self.shared_paths = paths;
+        self
+    }
+
+    pub fn ignored_authors(&mut self, authors: Vec<String>) -> &mut Self {
+        self.ignored_authors = authors;
+        self
+    }
+
+    pub fn ignored_paths(&mut self, paths: Vec<PathBuf>) -> &mut Self {
+        self.ignored_paths = paths;
+        self
+    }
+
+    pub fn tag_format(&mut self, format: TagFormat) -> &mut Self {
+        self.tag_format = Some(format);
+        self
+    }
+
+    pub fn first_parent(&mut self, first_parent: bool) -> &mut Self {
+        self.first_parent = first_parent;
+        self
+    }
+
+    pub fn bump(&mut self, bump: CommitType) -> &mut Self {
+        self.bump = Some(bump);
+        self
+    }
+
+    pub fn release_as(&mut self, version: Version) -> &mut Self {
+        self.release_as = Some(version);
+        self
+    }
+
+    pub fn bump_package(&mut self, name: String) -> &mut Self {
+        self.bump_package = Some(name);
+        self
+    }
+
+    pub fn initial_version(&mut self, initial: InitialVersion) -> &mut Self {
+        self.initial_version = Some(initial);
+        self
+    }
+
+    pub fn bump_rules(&mut self, rules: BumpRules) -> &mut Self {
+        self.bump_rules = rules;
+        self
+    }
+
+    pub fn changelog(&mut self, settings: changelog::Settings) -> &mut Self {
+        self.changelog = settings;
+        self
+    }
+
+    pub fn manifest(&mut self, path: PathBuf) -> &mut Self {
+        self.manifest = Some(path);
+        self
+    }
+
+    pub fn version_files(&mut self, files: Vec<VersionFile>) -> &mut Self {
+        self.version_files = files;
+        self
+    }
+
     pub fn build(self) -> Config {
         Config {
             user: self.user,
             repository_name: self.repository_name,
-            branch: self.branch.unwrap_or_else(|| "master".into()),
+            branch: self.branch,
+            branches: self.branches,
             repository_path: self.repository_path.unwrap(),
             write_mode: self.write_mode,
             release_mode: self.release_mode,
             repository: self.repository.unwrap(),
-            signature: self.signature.unwrap(),
+            signature: self.signature,
             gh_username: self.gh_username,
             gh_token: self.gh_token,
             cargo_token: self.cargo_token,
             remote: self.remote.unwrap_or_else(|| Err("No remote found".into())),
+            shared_paths: self.shared_paths,
+            ignored_authors: self.ignored_authors,
+            ignored_paths: self.ignored_paths,
+            tag_format: self.tag_format,
+            first_parent: self.first_parent,
+            bump: self.bump,
+            release_as: self.release_as,
+            bump_package: self.bump_package,
+            initial_version: self.initial_version,
+            bump_rules: self.bump_rules,
+            changelog: self.changelog,
+            manifest: self.manifest,
+            version_files: self.version_files,
         }
     }
 }
@@ -0,0 +1,299 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::toml_file::TomlError;
+use crate::version::VersionRange;
+
+pub const CONFIG_FILE: &str = ".semantic-rs.toml";
+
+/// Optional settings, read from `.semantic-rs.toml` in the repository root.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    /// Paths whose changes count towards the release of every crate in a workspace.
+    pub shared_paths: Vec<String>,
+    /// Authors, by name or email with `*` and `?` wildcards, whose commits are
+    /// ignored, like `*[bot]`.
+    pub ignore_authors: Vec<String>,
+    /// Paths whose changes alone don't count towards a release, like `docs`.
+    pub ignore_paths: Vec<String>,
+    /// Template for tag names, like `{crate}-v{version}`.
+    pub tag_format: Option<String>,
+    /// Only look for the previous release on the first-parent history of HEAD.
+    pub first_parent: bool,
+    /// Version of the first release, like `1.0.0`, or `current` for the version
+    /// in `Cargo.toml`. A major bump of that version if not set.
+    pub initial_version: Option<String>,
+    /// Bump levels of commits by `type` or `type(scope)`, like `perf = "patch"`.
+    pub bump_rules: BTreeMap<String, String>,
+    /// Bump level of all other commits, `none` unless configured.
+    pub default_bump: Option<String>,
+    /// Where to write a JSON manifest of the releases, relative to the repository root.
+    pub manifest: Option<String>,
+    /// Files besides `Cargo.toml` that mention the version, the `[[version_files]]` tables.
+    pub version_files: Vec<VersionFileConfig>,
+    /// Branches to release from, by name.
+    pub branches: BTreeMap<String, BranchConfig>,
+    pub changelog: ChangelogConfig,
+}
+
+/// How the changelog is rendered, the `[changelog]` table.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChangelogConfig {
+    /// Layout of the changelog file, `clog` or `keep-a-changelog`.
+    pub style: Option<String>,
+    /// Handlebars template, relative to the repository root.
+    pub template: Option<String>,
+    /// Sections in the order they are written, replacing the built-in ones.
+    pub sections: Option<Vec<SectionConfig>>,
+    /// Commit types left out of the changelog, even their breaking changes.
+    pub hidden_types: Vec<String>,
+    /// Wildcard patterns of contributor names and emails to leave out,
+    /// `["*[bot]"]` if not set.
+    pub exclude_contributors: Option<Vec<String>>,
+    /// The changelog file, relative to the crate's directory. An existing
+    /// `CHANGELOG.md`, `CHANGES.md` or `HISTORY.md` if not set.
+    pub path: Option<String>,
+    /// Whether to write a changelog file, true if not set.
+    pub file: Option<bool>,
+    pub links: LinksConfig,
+}
+
+/// A file whose version is updated on a release.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VersionFileConfig {
+    /// The file, relative to the repository root.
+    pub path: String,
+    /// Text with `{version}`, `{major}`, `{minor}` or `{patch}` placeholders.
+    pub pattern: Option<String>,
+    /// A regular expression whose first group is the version.
+    pub regex: Option<String>,
+    /// The crate whose version the file mentions, required in a workspace.
+    #[serde(rename = "crate")]
+    pub crate_name: Option<String>,
+}
+
+/// URL templates for links in the changelog, the `[changelog.links]` table.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LinksConfig {
+    /// Page of a commit, like `{repository}/commit/{hash}`.
+    pub commit: Option<String>,
+    /// Changes between tags, like `{repository}/compare/{previous_tag}...{tag}`.
+    pub compare: Option<String>,
+    /// Page of an issue, like `{repository}/issues/{id}`.
+    pub issue: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SectionConfig {
+    pub title: String,
+    /// Commit types listed in this section, like `feat`.
+    pub types: Vec<String>,
+    /// List breaking changes in this section, first.
+    #[serde(default)]
+    pub breaking: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BranchConfig {
+    /// Pre-release channel, e.g. `beta` for releases like `1.3.0-beta.1`.
+    pub prerelease: Option<String>,
+    /// Versions a maintenance branch may release, e.g. `1.x` or `1.4.x`.
+    pub range: Option<String>,
+}
+
+impl BranchConfig {
+    /// The configured range, or the branch's name if that is a range, like `1.x`.
+    pub fn range(&self, branch: Option<&str>) -> Result<Option<VersionRange>, String> {
+        match self.range {
+            Some(ref range) => VersionRange::parse(range).map(Some),
+            None => Ok(branch.and_then(|branch| VersionRange::parse(branch).ok())),
+        }
+    }
+}
+
+/// The settings of the checked out branch. A branch that isn't configured, or
+/// a detached HEAD, gets the defaults.
+pub fn branch_config(
+    branches: &BTreeMap<String, BranchConfig>,
+    branch: Option<&str>,
+) -> BranchConfig {
+    branch
+        .and_then(|branch| branches.get(branch))
+        .cloned()
+        .unwrap_or_default()
+}
+
+pub fn read(repository_path: &str) -> Result<ConfigFile, TomlError> {
+    let file_path = Path::new(repository_path).join(CONFIG_FILE);
+    let contents = match fs::read_to_string(file_path) {
+        Ok(contents) => contents,
+        Err(ref err) if err.kind() == ErrorKind::NotFound => return Ok(ConfigFile::default()),
+        Err(err) => return Err(TomlError::Io(err)),
+    };
+
+    toml::from_str(&contents).map_err(TomlError::Deserialize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_shared_paths() {
+        let config: ConfigFile = toml::from_str(r#"shared_paths = ["Cargo.lock"]"#).unwrap();
+        assert_eq!(config.shared_paths, vec!["Cargo.lock".to_string()]);
+    }
+
+    #[test]
+    fn reads_ignored_commits() {
+        let config: ConfigFile = toml::from_str(
+            r#"
+            ignore_authors = ["dependabot[bot]"]
+            ignore_paths = ["docs", ".github"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.ignore_authors, vec!["dependabot[bot]".to_string()]);
+        assert_eq!(
+            config.ignore_paths,
+            vec!["docs".to_string(), ".github".into()]
+        );
+    }
+
+    #[test]
+    fn reads_version_files() {
+        let config: ConfigFile = toml::from_str(
+            r#"
+            [[version_files]]
+            path = "README.md"
+            pattern = 'foo = "{major}.{minor}"'
+
+            [[version_files]]
+            path = "foo/src/lib.rs"
+            regex = 'docs.rs/foo/([^"]+)'
+            crate = "foo"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.version_files.len(), 2);
+        assert_eq!(config.version_files[0].path, "README.md");
+        assert_eq!(config.version_files[0].regex, None);
+        assert_eq!(
+            config.version_files[1].regex.as_deref(),
+            Some(r#"docs.rs/foo/([^"]+)"#)
+        );
+        assert_eq!(config.version_files[1].crate_name.as_deref(), Some("foo"));
+    }
+
+    #[test]
+    fn reads_changelog_settings() {
+        let config: ConfigFile = toml::from_str(
+            r#"
+            [changelog]
+            style = "keep-a-changelog"
+            template = "changelog.hbs"
+            hidden_types = ["chore"]
+            exclude_contributors = ["*[bot]", "ci@example.com"]
+            path = "docs/CHANGES.md"
+            file = false
+            sections = [
+                { title = "Added", types = ["feat"] },
+                { title = "Fixed", types = ["fix", "perf"], breaking = true },
+            ]
+
+            [changelog.links]
+            commit = "{repository}/-/commit/{hash}"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.changelog.template.as_deref(), Some("changelog.hbs"));
+        assert_eq!(config.changelog.hidden_types, vec!["chore".to_string()]);
+        assert_eq!(config.changelog.path.as_deref(), Some("docs/CHANGES.md"));
+        assert_eq!(config.changelog.file, Some(false));
+        assert_eq!(
+            config.changelog.exclude_contributors,
+            Some(vec!["*[bot]".to_string(), "ci@example.com".into()])
+        );
+        let sections = config.changelog.sections.unwrap();
+        assert_eq!(sections[1].title, "Fixed");
+        assert_eq!(sections[1].types, vec!["fix".to_string(), "perf".into()]);
+        assert!(sections[1].breaking && !sections[0].breaking);
+        assert_eq!(
+            config.changelog.links.commit.as_deref(),
+            Some("{repository}/-/commit/{hash}")
+        );
+        assert_eq!(config.changelog.links.issue, None);
+    }
+
+    #[test]
+    fn reads_branches() {
+        let config: ConfigFile = toml::from_str(
+            r#"
+            [branches]
+            master = {}
+            next = { prerelease = "beta" }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.branches["master"].prerelease, None);
+        assert_eq!(config.branches["next"].prerelease, Some("beta".into()));
+        assert_eq!(
+            branch_config(&config.branches, Some("next")).prerelease,
+            Some("beta".into())
+        );
+        assert_eq!(branch_config(&config.branches, None).prerelease, None);
+    }
+
+    #[test]
+    fn maintenance_branch_ranges() {
+        let config = BranchConfig::default();
+        assert_eq!(config.range(Some("master")), Ok(None));
+        assert_eq!(config.range(None), Ok(None));
+        assert_eq!(
+            config.range(Some("1.4.x")),
+            VersionRange::parse("1.4.x").map(Some)
+        );
+
+        let config = BranchConfig {
+            range: Some("1.x".into()),
+            ..BranchConfig::default()
+        };
+        assert_eq!(
+            config.range(Some("support")),
+            VersionRange::parse("1.x").map(Some)
+        );
+    }
+
+    #[test]
+    fn reads_bump_rules() {
+        let config: ConfigFile = toml::from_str(
+            r#"
+            default_bump = "patch"
+
+            [bump_rules]
+            perf = "patch"
+            "fix(internal)" = "none"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.default_bump.as_deref(), Some("patch"));
+        assert_eq!(config.bump_rules["fix(internal)"], "none");
+    }
+
+    #[test]
+    fn rejects_unknown_settings() {
+        assert!(toml::from_str::<ConfigFile>("shared_path = []").is_err());
+    }
+}
@@ -0,0 +1,309 @@
+use std::fmt;
+
+/// A commit message following the Conventional Commits 1.0 specification:
+///
+/// ```text
+/// type(scope)!: description
+///
+/// body
+///
+/// Token: value
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConventionalCommit {
+    /// The type in lowercase, e.g. `feat` or `fix`.
+    pub commit_type: String,
+    pub scope: Option<String>,
+    /// Set by a `!` before the colon or a `BREAKING CHANGE` footer.
+    pub breaking: bool,
+    pub description: String,
+    pub body: Option<String>,
+    pub footers: Vec<Footer>,
+}
+
+/// A trailer like `Refs: #123`, `Closes #12` or `BREAKING CHANGE: ...`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Footer {
+    pub token: String,
+    pub value: String,
+}
+
+impl Footer {
+    /// `BREAKING CHANGE` and its synonym `BREAKING-CHANGE` have to be uppercase.
+    pub fn is_breaking_change(&self) -> bool {
+        self.token == "BREAKING CHANGE" || self.token == "BREAKING-CHANGE"
+    }
+}
+
+/// Why a commit message is not a Conventional Commit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    Empty,
+    MissingType,
+    InvalidType(String),
+    InvalidScope,
+    MissingSpace,
+    EmptyDescription,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "the commit message is empty"),
+            ParseError::MissingType => write!(f, "the header has no type, like `fix: ...`"),
+            ParseError::InvalidType(commit_type) => write!(
+                f,
+                "the type '{}' may only contain letters, like `feat` or `fix`",
+                commit_type
+            ),
+            ParseError::InvalidScope => write!(
+                f,
+                "the scope has to be a non-empty word in parentheses, like `fix(parser): ...`"
+            ),
+            ParseError::MissingSpace => write!(f, "the colon after the type needs a space"),
+            ParseError::EmptyDescription => write!(f, "the description after the type is empty"),
+        }
+    }
+}
+
+impl ConventionalCommit {
+    pub fn parse(message: &str) -> Result<ConventionalCommit, ParseError> {
+        let mut lines = message.lines();
+        let header = lines
+            .next()
+            .map(str::trim_end)
+            .filter(|header| !header.is_empty())
+            .ok_or(ParseError::Empty)?;
+        let (commit_type, scope, breaking, description) = parse_header(header)?;
+
+        // The spec asks for a blank line after the header, but a body or
+        // footer right below it is common enough to accept.
+        let lines = lines.map(str::trim_end).collect::<Vec<_>>();
+        let paragraphs = lines
+            .split(|line| line.is_empty())
+            .filter(|paragraph| !paragraph.is_empty())
+            .collect::<Vec<_>>();
+
+        // Footers are the trailing paragraphs that start with a footer.
+        let mut footer_start = paragraphs.len();
+        while footer_start > 0 && parse_footer(paragraphs[footer_start - 1][0]).is_some() {
+            footer_start -= 1;
+        }
+
+        let body = paragraphs[..footer_start]
+            .iter()
+            .map(|paragraph| paragraph.join("\n"))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        let mut footers: Vec<Footer> = vec![];
+        for line in paragraphs[footer_start..].iter().flat_map(|p| p.iter()) {
+            match (parse_footer(line), footers.last_mut()) {
+                (Some(footer), _) => footers.push(footer),
+                // A value can continue on the following lines.
+                (None, Some(footer)) => {
+                    footer.value.push('\n');
+                    footer.value.push_str(line);
+                }
+                (None, None) => unreachable!("Footers start with a footer line"),
+            }
+        }
+
+        Ok(ConventionalCommit {
+            commit_type,
+            scope,
+            breaking: breaking || footers.iter().any(Footer::is_breaking_change),
+            description,
+            body: Some(body).filter(|body| !body.is_empty()),
+            footers,
+        })
+    }
+
+    /// The value of the first footer with this token, ignoring case.
+    pub fn footer(&self, token: &str) -> Option<&str> {
+        self.footers
+            .iter()
+            .find(|footer| footer.token.eq_ignore_ascii_case(token))
+            .map(|footer| footer.value.as_str())
+    }
+
+    /// What breaks: the `BREAKING CHANGE` footers, or the description
+    /// of a commit marked with `!` alone.
+    pub fn breaking_changes(&self) -> Vec<&str> {
+        let footers = self
+            .footers
+            .iter()
+            .filter(|footer| footer.is_breaking_change())
+            .map(|footer| footer.value.as_str())
+            .collect::<Vec<_>>();
+
+        if footers.is_empty() && self.breaking {
+            vec![self.description.as_str()]
+        } else {
+            footers
+        }
+    }
+}
+
+fn parse_header(header: &str) -> Result<(String, Option<String>, bool, String), ParseError> {
+    let colon = header.find(':').ok_or(ParseError::MissingType)?;
+    let (prefix, rest) = (&header[..colon], &header[colon + 1..]);
+
+    let (prefix, breaking) = match prefix.strip_suffix('!') {
+        Some(prefix) => (prefix, true),
+        None => (prefix, false),
+    };
+
+    let (commit_type, scope) = match prefix.find('(') {
+        Some(open) => {
+            let scope = prefix[open + 1..]
+                .strip_suffix(')')
+                .map(str::trim)
+                .filter(|scope| !scope.is_empty() && !scope.contains(['(', ')']))
+                .ok_or(ParseError::InvalidScope)?;
+            (&prefix[..open], Some(scope.to_owned()))
+        }
+        None => (prefix, None),
+    };
+
+    if commit_type.is_empty() {
+        return Err(ParseError::MissingType);
+    }
+    if !commit_type.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(ParseError::InvalidType(commit_type.to_owned()));
+    }
+
+    if rest.trim().is_empty() {
+        return Err(ParseError::EmptyDescription);
+    }
+    let description = rest.strip_prefix(' ').ok_or(ParseError::MissingSpace)?;
+
+    Ok((
+        commit_type.to_ascii_lowercase(),
+        scope,
+        breaking,
+        description.trim().to_owned(),
+    ))
+}
+
+/// Parses a line like `Token: value` or `Token #value`.
+fn parse_footer(line: &str) -> Option<Footer> {
+    let (token, value) = match line.strip_prefix("BREAKING CHANGE: ") {
+        Some(value) => ("BREAKING CHANGE", value),
+        None => {
+            let end = line.find(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))?;
+            let value = line[end..]
+                .strip_prefix(": ")
+                .or_else(|| line[end..].strip_prefix(" #"))?;
+            (&line[..end], value)
+        }
+    };
+
+    if token.is_empty() {
+        return None;
+    }
+
+    Some(Footer {
+        token: token.to_owned(),
+        value: value.trim().to_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(message: &str) -> ConventionalCommit {
+        ConventionalCommit::parse(message).unwrap()
+    }
+
+    #[test]
+    fn parses_header() {
+        let commit = parse("feat(parser)!: Support footers");
+        assert_eq!(commit.commit_type, "feat");
+        assert_eq!(commit.scope.as_deref(), Some("parser"));
+        assert!(commit.breaking);
+        assert_eq!(commit.description, "Support footers");
+        assert_eq!(commit.body, None);
+        assert_eq!(commit.breaking_changes(), vec!["Support footers"]);
+
+        let commit = parse("FIX: Handle empty input");
+        assert_eq!(commit.commit_type, "fix");
+        assert_eq!(commit.scope, None);
+        assert!(!commit.breaking);
+    }
+
+    #[test]
+    fn parses_body_and_footers() {
+        let commit = parse(
+            "fix: Prevent racing of requests
+
+Introduce a request id and a reference to latest request.
+
+Remove timeouts which were used to mitigate the racing issue.
+
+Reviewed-by: Z
+Refs #123
+BREAKING-CHANGE: Requests are no longer
+  retried after a timeout.",
+        );
+
+        assert_eq!(
+            commit.body.as_deref(),
+            Some(
+                "Introduce a request id and a reference to latest request.\n\n\
+                 Remove timeouts which were used to mitigate the racing issue."
+            )
+        );
+        assert_eq!(commit.footer("reviewed-by"), Some("Z"));
+        assert_eq!(commit.footer("Refs"), Some("123"));
+        assert!(commit.breaking);
+        assert_eq!(
+            commit.breaking_changes(),
+            vec!["Requests are no longer\n  retried after a timeout."]
+        );
+    }
+
+    #[test]
+    fn breaking_change_footer_is_uppercase() {
+        let commit = parse("feat: Add option\n\nBREAKING CHANGE: The old option is gone");
+        assert!(commit.breaking);
+
+        let commit = parse("feat: Add option\n\nbreaking change: nothing, really");
+        assert!(!commit.breaking);
+        assert!(commit.footers.is_empty());
+    }
+
+    #[test]
+    fn body_paragraphs_are_no_footers() {
+        let commit = parse("docs: Explain things\n\nNote: this is the body.\n\nMore body.");
+        assert!(commit.footers.is_empty());
+        assert_eq!(
+            commit.body.as_deref(),
+            Some("Note: this is the body.\n\nMore body.")
+        );
+    }
+
+    #[test]
+    fn accepts_missing_blank_line() {
+        let commit = parse("feat: Add option\nBREAKING CHANGE: The old one is gone");
+        assert_eq!(commit.body, None);
+        assert!(commit.breaking);
+    }
+
+    #[test]
+    fn rejects_invalid_messages() {
+        let error = |message| ConventionalCommit::parse(message).unwrap_err();
+
+        assert_eq!(error(""), ParseError::Empty);
+        assert_eq!(error("Fix the bug"), ParseError::MissingType);
+        assert_eq!(
+            error("Merge branch 'a' of github.com:foo/bar"),
+            ParseError::InvalidType("Merge branch 'a' of github.com".into())
+        );
+        assert_eq!(error("fix(): Oops"), ParseError::InvalidScope);
+        assert_eq!(error("fix(parser: Oops"), ParseError::InvalidScope);
+        assert_eq!(error("fix:Oops"), ParseError::MissingSpace);
+        assert_eq!(error("fix: "), ParseError::EmptyDescription);
+    }
+}
@@ -0,0 +1,158 @@
+use serde::Serialize;
+
+use crate::commit_analyzer::{CommitRecord, CommitType};
+
+/// Why a crate gets the bump it gets, as reported by `semantic-rs explain`.
+#[derive(Debug, Serialize)]
+pub struct Explanation {
+    #[serde(rename = "crate")]
+    pub name: String,
+    pub version: String,
+    /// Tag of the release the commits are analyzed from.
+    pub base_tag: Option<String>,
+    /// Why that tag was chosen, or why there is none.
+    pub base_reason: String,
+    pub bump: CommitType,
+    pub release_as: Option<String>,
+    pub commits: Vec<CommitRecord>,
+}
+
+fn or_dash(value: Option<&str>) -> &str {
+    value.unwrap_or("-")
+}
+
+/// Pads every column but the last to the width of its widest cell.
+fn columns(rows: &[Vec<String>]) -> String {
+    let count = rows.first().map_or(0, Vec::len);
+    let widths = (0..count)
+        .map(|column| rows.iter().map(|row| row[column].len()).max().unwrap_or(0))
+        .collect::<Vec<_>>();
+
+    rows.iter()
+        .map(|row| {
+            let mut line = String::from("  ");
+            for (column, cell) in row.iter().enumerate() {
+                if column + 1 < count {
+                    line.push_str(&format!("{:width$}  ", cell, width = widths[column]));
+                } else {
+                    line.push_str(cell);
+                }
+            }
+            line.trim_end().to_owned() + "\n"
+        })
+        .collect()
+}
+
+fn commit_row(record: &CommitRecord) -> Vec<String> {
+    let bump = match record.skipped {
+        Some(_) => "-",
+        None => record.bump.level(),
+    };
+    let note = match (&record.skipped, &record.parse_error) {
+        (Some(reason), _) => format!(" (skipped: {})", reason),
+        (None, Some(error)) => format!(" (default level: {})", error),
+        (None, None) => String::new(),
+    };
+
+    vec![
+        record.sha[..8].to_owned(),
+        or_dash(record.commit_type.as_deref()).to_owned(),
+        or_dash(record.scope.as_deref()).to_owned(),
+        if record.breaking { "yes" } else { "no" }.to_owned(),
+        bump.to_owned(),
+        format!("{}{}", record.subject, note),
+    ]
+}
+
+/// Renders the explanations as a table per crate.
+pub fn table(explanations: &[Explanation]) -> String {
+    let mut out = String::new();
+
+    for explanation in explanations {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&format!("{} {}\n", explanation.name, explanation.version));
+        out.push_str(&format!(
+            "  Base: {} ({})\n",
+            or_dash(explanation.base_tag.as_deref()),
+            explanation.base_reason
+        ));
+        out.push_str(&format!("  Bump: {}\n", explanation.bump.level()));
+        if let Some(ref release_as) = explanation.release_as {
+            out.push_str(&format!("  Release as: {}\n", release_as));
+        }
+        out.push('\n');
+
+        if explanation.commits.is_empty() {
+            out.push_str("  No commits since the base tag\n");
+            continue;
+        }
+
+        let header = ["COMMIT", "TYPE", "SCOPE", "BREAKING", "BUMP", "SUBJECT"];
+        let mut rows = vec![header.iter().map(|cell| cell.to_string()).collect()];
+        rows.extend(explanation.commits.iter().map(commit_row));
+        out.push_str(&columns(&rows));
+    }
+
+    out
+}
+
+/// Renders the explanations as a JSON array.
+pub fn json(explanations: &[Explanation]) -> String {
+    serde_json::to_string_pretty(explanations).expect("Serializing the explanation failed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::commit_analyzer::BumpRules;
+
+    fn explanation() -> Explanation {
+        let rules = BumpRules::default();
+        let mut reverted = CommitRecord::new("b".repeat(40), "feat: Add X", &rules);
+        reverted.skipped = Some("reverted by cccccccc".into());
+
+        Explanation {
+            name: "foo".into(),
+            version: "1.0.0".into(),
+            base_tag: Some("v1.0.0".into()),
+            base_reason: "the latest release tag reachable from HEAD".into(),
+            bump: CommitType::Patch,
+            release_as: None,
+            commits: vec![
+                CommitRecord::new("a".repeat(40), "fix(parser): Handle tabs", &rules),
+                reverted,
+                CommitRecord::new("d".repeat(40), "Update README", &rules),
+            ],
+        }
+    }
+
+    #[test]
+    fn renders_table() {
+        assert_eq!(
+            table(&[explanation()]),
+            "foo 1.0.0
+  Base: v1.0.0 (the latest release tag reachable from HEAD)
+  Bump: patch
+
+  COMMIT    TYPE  SCOPE   BREAKING  BUMP   SUBJECT
+  aaaaaaaa  fix   parser  no        patch  fix(parser): Handle tabs
+  bbbbbbbb  feat  -       no        -      feat: Add X (skipped: reverted by cccccccc)
+  dddddddd  -     -       no        none   Update README (default level: the header has no type, like `fix: ...`)
+"
+        );
+    }
+
+    #[test]
+    fn renders_json() {
+        let json: serde_json::Value = serde_json::from_str(&json(&[explanation()])).unwrap();
+        assert_eq!(json[0]["crate"], "foo");
+        assert_eq!(json[0]["bump"], "patch");
+        assert_eq!(json[0]["commits"][0]["type"], "fix");
+        assert_eq!(json[0]["commits"][0]["bump"], "patch");
+        assert_eq!(json[0]["commits"][1]["skipped"], "reverted by cccccccc");
+        assert_eq!(json[0]["commits"][2]["type"], serde_json::Value::Null);
+    }
+}
@@ -1,12 +1,29 @@
+use std::collections::HashSet;
 use std::env;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use git2::{self, Commit, Cred, PushOptions, RemoteCallbacks, Repository, Signature};
+use git2::{
+    self, Commit, Cred, Oid, PushOptions, RemoteCallbacks, Repository, Signature, Sort, Time,
+};
 use semver::Version;
 
-use crate::commit_analyzer::{self, CommitType};
+use crate::commit_analyzer::{self, BumpRules, CommitRecord, CommitType};
 use crate::config::Config;
 use crate::error::Error;
+use crate::tag_format::TagFormat;
+use crate::utils::wildcard_match;
+
+/// The checked out branch, None on a detached HEAD.
+pub fn current_branch(repo: &Repository) -> Option<String> {
+    let head = repo.head().expect("No HEAD found for repository");
+
+    if head.is_branch() {
+        let short = head.shorthand().expect("No branch name found");
+        return Some(short.into());
+    }
+
+    None
+}
 
 pub fn get_signature(repo: &Repository) -> Result<Signature, Error> {
     let author = {
@@ -32,12 +49,12 @@ pub fn get_signature(repo: &Repository) -> Result<Signature, Error> {
     Signature::now(&author, &email).map_err(From::from)
 }
 
-fn range_to_head(commit: &str) -> String {
-    format!("{}..HEAD", commit)
-}
-
-fn format_commit(commit: Commit) -> String {
-    format!("{}\n{}", commit.id(), commit.message().unwrap_or(""))
+/// The commits from `from` up to `to`, or the whole history of `to` without a start.
+fn range(from: Option<&str>, to: &str) -> String {
+    match from {
+        Some(from) => format!("{}..{}", from, to),
+        None => to.to_owned(),
+    }
 }
 
 fn add<P: AsRef<Path>>(repo: &Repository, files: &[P]) -> Result<(), git2::Error> {
@@ -50,8 +67,22 @@ fn add<P: AsRef<Path>>(repo: &Repository, files: &[P]) -> Result<(), git2::Error
     index.write()
 }
 
+fn signature(config: &Config) -> Result<&Signature<'static>, git2::Error> {
+    config
+        .signature
+        .as_ref()
+        .ok_or_else(|| git2::Error::from_str("The committer is only looked up in write mode"))
+}
+
+fn branch(config: &Config) -> Result<&str, git2::Error> {
+    config
+        .branch
+        .as_deref()
+        .ok_or_else(|| git2::Error::from_str("Could not determine current branch"))
+}
+
 fn commit(config: &Config, message: &str) -> Result<(), git2::Error> {
-    let update_ref = format!("refs/heads/{}", config.branch);
+    let update_ref = format!("refs/heads/{}", branch(config)?);
     let repo = &config.repository;
 
     let oid = repo.refname_to_id("HEAD")?;
@@ -62,10 +93,11 @@ fn commit(config: &Config, message: &str) -> Result<(), git2::Error> {
     let tree_oid = index.write_tree()?;
     let tree = repo.find_tree(tree_oid)?;
 
+    let signature = signature(config)?;
     repo.commit(
         Some(&update_ref),
-        &config.signature,
-        &config.signature,
+        signature,
+        signature,
         message,
         &tree,
         &parents,
@@ -76,10 +108,10 @@ fn commit(config: &Config, message: &str) -> Result<(), git2::Error> {
 fn create_tag(config: &Config, tag_name: &str, message: &str) -> Result<(), git2::Error> {
     let repo = &config.repository;
 
-    let rev = format!("refs/heads/{}", config.branch);
+    let rev = format!("refs/heads/{}", branch(config)?);
     let obj = repo.revparse_single(&rev)?;
 
-    repo.tag(tag_name, &obj, &config.signature, message, false)
+    repo.tag(tag_name, &obj, signature(config)?, message, false)
         .map(|_| ())
 }
 
@@ -91,82 +123,518 @@ fn is_https_remote(maybe_remote: Option<&str>) -> bool {
     }
 }
 
-pub fn latest_tag(repo: &Repository) -> Option<Version> {
+/// All release tags of a crate, with their versions.
+pub fn tagged_versions(
+    repo: &Repository,
+    tag_format: &TagFormat,
+    crate_name: &str,
+) -> Vec<(Version, String)> {
     let tags = match repo.tag_names(None) {
         Ok(tags) => tags,
-        Err(_) => return None,
+        Err(_) => return vec![],
     };
 
     tags.iter()
         .map(|tag| tag.unwrap())
-        .filter_map(|tag| Version::parse(&tag[1..]).ok())
-        .max()
+        .filter_map(|tag| {
+            tag_format
+                .parse(crate_name, tag)
+                .map(|version| (version, tag.to_owned()))
+        })
+        .collect()
+}
+
+/// All commits reachable from HEAD, optionally following first parents only.
+fn reachable_commits(repo: &Repository, first_parent: bool) -> HashSet<Oid> {
+    let mut walker = repo.revwalk().expect("Creating a revwalk failed");
+    walker
+        .set_sorting(Sort::TOPOLOGICAL)
+        .expect("Sorting the revwalk failed");
+    if first_parent {
+        walker
+            .simplify_first_parent()
+            .expect("Simplifying the revwalk failed");
+    }
+    walker.push_head().expect("Adding HEAD failed");
+
+    walker.map(|oid| oid.expect("Not a valid commit")).collect()
+}
+
+/// When a tag was created: the tagger's time for an annotated tag, the
+/// commit's time for a lightweight one.
+pub fn tag_time(repo: &Repository, tag: &str) -> Option<Time> {
+    let object = repo.revparse_single(&format!("refs/tags/{}", tag)).ok()?;
+    if let Some(tagger) = object.as_tag().and_then(|tag| tag.tagger()) {
+        return Some(tagger.when());
+    }
+    object.peel_to_commit().ok().map(|commit| commit.time())
+}
+
+fn tagged_commit(repo: &Repository, tag: &str) -> Option<Oid> {
+    repo.revparse_single(&format!("refs/tags/{}", tag))
+        .and_then(|object| object.peel_to_commit())
+        .map(|commit| commit.id())
+        .ok()
 }
 
-pub fn version_bump_since_latest(repo: &Repository) -> CommitType {
-    match latest_tag(repo) {
-        Some(t) => {
-            let tag = format!("v{}", t.to_string());
-            version_bump_since_tag(repo, &tag)
+/// Finds the tag of the crate's latest release among the versions accepted by `filter`.
+///
+/// Only tags on commits reachable from HEAD count, so releases on other branches
+/// or tags fetched from forks are ignored. With `first_parent`, releases merged
+/// in from other branches are ignored as well.
+pub fn latest_tag<F>(
+    repo: &Repository,
+    tag_format: &TagFormat,
+    crate_name: &str,
+    first_parent: bool,
+    filter: F,
+) -> Option<(Version, String)>
+where
+    F: Fn(&Version) -> bool,
+{
+    let tags = tagged_versions(repo, tag_format, crate_name)
+        .into_iter()
+        .filter(|(version, _)| filter(version))
+        .collect::<Vec<_>>();
+    if tags.is_empty() {
+        return None;
+    }
+
+    let history = if first_parent {
+        "the first-parent history of HEAD"
+    } else {
+        "HEAD"
+    };
+    let reachable = reachable_commits(repo, first_parent);
+    let (reachable_tags, unreachable_tags): (Vec<_>, Vec<_>) =
+        tags.into_iter().partition(|(_, tag)| {
+            tagged_commit(repo, tag).map_or(false, |commit| reachable.contains(&commit))
+        });
+    let latest = reachable_tags.into_iter().max();
+
+    let ignored = unreachable_tags
+        .into_iter()
+        .filter(|(version, _)| latest.as_ref().map_or(true, |(latest, _)| version > latest))
+        .map(|(_, tag)| tag)
+        .collect::<Vec<_>>();
+    if !ignored.is_empty() {
+        info!(
+            "Ignoring tags of {} not reachable from {}: {}",
+            crate_name,
+            history,
+            ignored.join(", ")
+        );
+    }
+    if let Some((_, ref tag)) = latest {
+        info!(
+            "Previous release of {} is {}, the latest release tag reachable from {}",
+            crate_name, tag, history
+        );
+    }
+
+    latest
+}
+
+/// Which commits count towards a crate's release.
+#[derive(Debug, Clone, Default)]
+pub struct CommitFilter {
+    /// Paths below which a commit has to change something. Empty for every commit.
+    pub paths: Vec<PathBuf>,
+    /// Paths below `paths` that belong to other crates.
+    pub excluded_paths: Vec<PathBuf>,
+    /// Authors, by name or email with `*` and `?` wildcards, whose commits are
+    /// ignored, like bots.
+    pub ignored_authors: Vec<String>,
+    /// Paths whose changes alone don't count, like `docs`.
+    pub ignored_paths: Vec<PathBuf>,
+}
+
+impl CommitFilter {
+    /// Why a commit doesn't count, or `None` if it does.
+    ///
+    /// Merge commits are compared against their first parent.
+    pub fn skip_reason(&self, repo: &Repository, commit: &Commit) -> Option<String> {
+        if commit_analyzer::skips_release(commit.message().unwrap_or("")) {
+            return Some("marked with [skip release]".to_owned());
         }
+
+        let author = commit.author();
+        let name = author.name().unwrap_or("");
+        let email = author.email().unwrap_or("").to_ascii_lowercase();
+        let is_ignored = self.ignored_authors.iter().any(|pattern| {
+            wildcard_match(pattern, name) || wildcard_match(&pattern.to_ascii_lowercase(), &email)
+        });
+        if is_ignored {
+            return Some(format!("authored by {}", name));
+        }
+
+        if self.paths.is_empty() && self.excluded_paths.is_empty() && self.ignored_paths.is_empty()
+        {
+            return None;
+        }
+
+        let below =
+            |file: &Path, paths: &[PathBuf]| paths.iter().any(|path| file.starts_with(path));
+        let (excluded, files): (Vec<_>, Vec<_>) = changed_files(repo, commit)
+            .into_iter()
+            .filter(|file| self.paths.is_empty() || below(file, &self.paths))
+            .partition(|file| below(file, &self.excluded_paths));
+
+        if files.is_empty() && !excluded.is_empty() {
+            Some(format!(
+                "only changes in {}",
+                display_paths(&self.excluded_paths)
+            ))
+        } else if files.is_empty() {
+            if self.paths.is_empty() {
+                None
+            } else {
+                Some(format!("no changes in {}", display_paths(&self.paths)))
+            }
+        } else if files.iter().all(|file| below(file, &self.ignored_paths)) {
+            Some(format!(
+                "only changes in {}",
+                display_paths(&self.ignored_paths)
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+fn display_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The files a commit added, changed or deleted.
+fn changed_files(repo: &Repository, commit: &Commit) -> Vec<PathBuf> {
+    let tree = commit.tree().expect("No tree found for commit");
+    let parent_tree = commit
+        .parent(0)
+        .ok()
+        .map(|parent| parent.tree().expect("No tree found for parent commit"));
+
+    let diff = repo
+        .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)
+        .expect("Diffing commit failed");
+    diff.deltas()
+        .filter_map(|delta| delta.new_file().path().or_else(|| delta.old_file().path()))
+        .map(Path::to_path_buf)
+        .collect()
+}
+
+/// Determines the bump since the previous release, or `Major` if there is none.
+pub fn version_bump_since(
+    repo: &Repository,
+    tag: Option<&str>,
+    filter: &CommitFilter,
+    rules: &BumpRules,
+) -> CommitType {
+    match tag {
+        Some(tag) => version_bump_since_tag(repo, tag, filter, rules),
         None => CommitType::Major,
     }
 }
 
-pub fn version_bump_since_tag(repo: &Repository, tag: &str) -> CommitType {
-    let tag = range_to_head(tag);
+/// Finds the commits that are reverted by a later commit in the list, as
+/// `(revert, reverted)` pairs. `commits` are newest first, so reverting a
+/// revert brings the original commit back.
+fn reverts(commits: &[Commit]) -> Vec<(Oid, Oid)> {
+    let mut cancelled = HashSet::new();
+    let mut pairs = vec![];
 
-    let mut walker = repo.revwalk().expect("Creating a revwalk failed");
-    walker.push_range(&tag).expect("Adding a range failed");
+    for (index, commit) in commits.iter().enumerate() {
+        if cancelled.contains(&commit.id()) {
+            continue;
+        }
 
-    walker
-        .map(|c| {
-            repo.find_commit(c.expect("Not a valid commit"))
-                .expect("No commit found")
+        let message = commit.message().unwrap_or("");
+        let hashes = commit_analyzer::reverted_hashes(message);
+        let header = commit_analyzer::reverted_header(message);
+        if hashes.is_empty() && header.is_none() {
+            continue;
+        }
+
+        let reverted = commits[index + 1..].iter().find(|older| {
+            let id = older.id().to_string();
+            !cancelled.contains(&older.id())
+                && (hashes.iter().any(|hash| id.starts_with(hash.as_str()))
+                    || (header.is_some() && older.summary() == header))
+        });
+        if let Some(reverted) = reverted {
+            info!("Ignoring {} and its revert {}", reverted.id(), commit.id());
+            cancelled.insert(commit.id());
+            cancelled.insert(reverted.id());
+            pairs.push((commit.id(), reverted.id()));
+        }
+    }
+
+    pairs
+}
+
+/// Drops commits that are reverted by a later commit in the list, together with
+/// their reverts.
+fn without_reverted(commits: Vec<Commit>) -> Vec<Commit> {
+    let cancelled = reverts(&commits)
+        .into_iter()
+        .flat_map(|(revert, reverted)| vec![revert, reverted])
+        .collect::<HashSet<_>>();
+
+    commits
+        .into_iter()
+        .filter(|commit| !cancelled.contains(&commit.id()))
+        .collect()
+}
+
+/// Commits in a range like `origin/master..HEAD`, newest first.
+/// A single revision stands for its whole history.
+pub fn commits_in_range<'r>(
+    repo: &'r Repository,
+    range: &str,
+) -> Result<Vec<Commit<'r>>, git2::Error> {
+    let mut walker = repo.revwalk()?;
+    walker.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+    if range.contains("..") {
+        walker.push_range(range)?;
+    } else {
+        walker.push(repo.revparse_single(range)?.peel_to_commit()?.id())?;
+    }
+
+    walker.map(|oid| repo.find_commit(oid?)).collect()
+}
+
+/// All commits since `tag`, or the whole history, that pass the filter, newest first.
+///
+/// Commits reverted within the range are left out, together with their reverts.
+pub fn commits_since<'r>(
+    repo: &'r Repository,
+    tag: Option<&str>,
+    filter: &CommitFilter,
+) -> Result<Vec<Commit<'r>>, git2::Error> {
+    commits_between(repo, tag, "HEAD", filter)
+}
+
+/// Like `commits_since`, but up to the revision `to` instead of HEAD.
+pub fn commits_between<'r>(
+    repo: &'r Repository,
+    from: Option<&str>,
+    to: &str,
+    filter: &CommitFilter,
+) -> Result<Vec<Commit<'r>>, git2::Error> {
+    let commits = commits_in_range(repo, &range(from, to))?
+        .into_iter()
+        .filter(|commit| filter.skip_reason(repo, commit).is_none())
+        .collect();
+
+    Ok(without_reverted(commits))
+}
+
+/// The people named in `Co-authored-by` trailers, with their emails.
+fn co_authors(message: &str) -> Vec<(String, String)> {
+    message
+        .lines()
+        .filter_map(|line| {
+            let colon = line.find(':')?;
+            if !line[..colon].trim().eq_ignore_ascii_case("co-authored-by") {
+                return None;
+            }
+            let value = line[colon + 1..].trim();
+            let (name, email) = match value.find('<') {
+                Some(start) => (
+                    &value[..start],
+                    value[start + 1..].trim_end().trim_end_matches('>'),
+                ),
+                None => (value, ""),
+            };
+            Some((name.trim().to_owned(), email.trim().to_owned()))
+        })
+        .filter(|(name, _)| !name.is_empty())
+        .collect()
+}
+
+/// The authors and co-authors of `commits`, as named by the repository's
+/// `.mailmap`, in the order of their first commit. Commits come newest first.
+///
+/// Names or emails matching one of the `excluded` wildcard patterns, like
+/// `*[bot]`, are left out.
+pub fn contributors(repo: &Repository, commits: &[Commit], excluded: &[String]) -> Vec<String> {
+    let mailmap = repo.mailmap().ok();
+    let resolve = |name: &str, email: &str| {
+        let signature = Signature::now(name, email).ok()?;
+        let signature = match mailmap {
+            Some(ref mailmap) => mailmap.resolve_signature(&signature).ok()?,
+            None => signature,
+        };
+        Some((
+            signature.name().unwrap_or(name).to_owned(),
+            signature.email().unwrap_or(email).to_owned(),
+        ))
+    };
+    let is_excluded = |(name, email): &(String, String)| {
+        excluded
+            .iter()
+            .any(|pattern| wildcard_match(pattern, name) || wildcard_match(pattern, email))
+    };
+
+    let mut contributors: Vec<String> = vec![];
+    for commit in commits.iter().rev() {
+        let author = commit.author();
+        let people = std::iter::once((
+            author.name().unwrap_or("").to_owned(),
+            author.email().unwrap_or("").to_owned(),
+        ))
+        .chain(co_authors(commit.message().unwrap_or("")));
+
+        for (name, email) in people {
+            if let Some(person) = resolve(&name, &email) {
+                if !person.0.is_empty()
+                    && !is_excluded(&person)
+                    && !contributors.contains(&person.0)
+                {
+                    contributors.push(person.0);
+                }
+            }
+        }
+    }
+
+    contributors
+}
+
+/// Classifies every commit since `tag`, or in the whole history, newest first.
+///
+/// Commits the filter skips and commits reverted within the range are kept,
+/// marked with the reason they are skipped.
+pub fn analyze_since(
+    repo: &Repository,
+    tag: Option<&str>,
+    filter: &CommitFilter,
+    rules: &BumpRules,
+) -> Result<Vec<CommitRecord>, git2::Error> {
+    let commits = commits_in_range(repo, &range(tag, "HEAD"))?;
+    let skip_reasons = commits
+        .iter()
+        .map(|commit| filter.skip_reason(repo, commit))
+        .collect::<Vec<_>>();
+    let relevant = commits
+        .iter()
+        .zip(&skip_reasons)
+        .filter(|(_, reason)| reason.is_none())
+        .map(|(commit, _)| commit.clone())
+        .collect::<Vec<_>>();
+    let reverts = reverts(&relevant);
+    let short = |oid: &Oid| oid.to_string()[..8].to_owned();
+
+    let records = commits
+        .iter()
+        .zip(skip_reasons)
+        .map(|(commit, skip_reason)| {
+            let id = commit.id();
+            let mut record =
+                CommitRecord::new(id.to_string(), commit.message().unwrap_or(""), rules);
+            record.skipped = if skip_reason.is_some() {
+                skip_reason
+            } else if let Some((revert, _)) = reverts.iter().find(|(_, reverted)| *reverted == id) {
+                Some(format!("reverted by {}", short(revert)))
+            } else if let Some((_, reverted)) = reverts.iter().find(|(revert, _)| *revert == id) {
+                Some(format!("reverts {}", short(reverted)))
+            } else {
+                None
+            };
+            record
         })
-        .map(format_commit)
-        .map(|c| commit_analyzer::analyze_single(&c).expect("Analyzing commit failed"))
+        .collect();
+
+    Ok(records)
+}
+
+/// Determines the bump for all commits since `tag` that pass the filter.
+pub fn version_bump_since_tag(
+    repo: &Repository,
+    tag: &str,
+    filter: &CommitFilter,
+    rules: &BumpRules,
+) -> CommitType {
+    analyze_since(repo, Some(tag), filter, rules)
+        .expect("Walking the history failed")
+        .iter()
+        .map(CommitRecord::effective_bump)
         .max()
         .unwrap_or(CommitType::Unknown)
 }
 
-pub fn generate_commit_message(new_version: &str) -> String {
-    format!("Bump version to {}", new_version)
+/// The highest version requested by a `Release-As` footer since `tag`.
+pub fn release_as_since(
+    repo: &Repository,
+    tag: Option<&str>,
+    filter: &CommitFilter,
+) -> Option<Version> {
+    commits_since(repo, tag, filter)
+        .expect("Walking the history failed")
+        .iter()
+        .filter_map(|c| commit_analyzer::release_as(c.message().unwrap_or("")))
+        .max()
+}
+
+pub fn generate_commit_message(new_versions: &[String]) -> String {
+    match new_versions {
+        [new_version] => format!("Bump version to {}", new_version),
+        _ => format!("Bump versions to {}", new_versions.join(", ")),
+    }
 }
 
-pub fn commit_files(config: &Config, new_version: &str) -> Result<(), Error> {
+/// Commits the given files, relative to the repository root, with a version bump message.
+pub fn commit_files<P: AsRef<Path>>(
+    config: &Config,
+    files: &[P],
+    new_versions: &[String],
+) -> Result<(), Error> {
     let repo = &config.repository;
-    let files = ["Cargo.toml", "Cargo.lock", "Changelog.md"];
     let files = files
         .iter()
-        .filter(|filename| {
-            let path = Path::new(filename);
-            !repo
-                .status_should_ignore(path)
-                .expect("Determining ignore status of file failed")
+        .map(|path| path.as_ref())
+        .filter(|path| {
+            Path::new(&config.repository_path).join(path).exists()
+                && !repo
+                    .status_should_ignore(path)
+                    .expect("Determining ignore status of file failed")
         })
         .collect::<Vec<_>>();
 
     add(&config.repository, &files[..])?;
 
-    commit(config, &generate_commit_message(new_version)).map_err(Error::from)
+    commit(config, &generate_commit_message(new_versions)).map_err(Error::from)
+}
+
+/// Commits the release manifest after the release commit it names.
+pub fn commit_manifest(config: &Config, path: &Path, new_versions: &[String]) -> Result<(), Error> {
+    add(&config.repository, &[path])?;
+
+    let message = format!("Add release manifest for {}", new_versions.join(", "));
+    commit(config, &message).map_err(Error::from)
 }
 
 pub fn tag(config: &Config, tag_name: &str, tag_message: &str) -> Result<(), Error> {
     create_tag(config, &tag_name, &tag_message).map_err(Error::from)
 }
 
-pub fn push(config: &Config, tag_name: &str) -> Result<(), Error> {
+pub fn push(config: &Config, tag_names: &[String]) -> Result<(), Error> {
     let repo = &config.repository;
 
-    let branch = &config.branch;
+    let branch = branch(config)?;
     let gh_username = config.gh_username.as_ref();
     let gh_token = config.gh_token.as_ref();
 
-    // We need to push both the branch we just committed as well as the tag we created.
-    let branch_ref = format!("refs/heads/{}", branch);
-    let tag_ref = format!("refs/tags/{}", tag_name);
-    let refs = [&branch_ref[..], &tag_ref[..]];
+    // We need to push both the branch we just committed as well as the tags we created.
+    let mut refs = vec![format!("refs/heads/{}", branch)];
+    refs.extend(
+        tag_names
+            .iter()
+            .map(|tag_name| format!("refs/tags/{}", tag_name)),
+    );
 
     let mut remote = repo.find_remote("origin")?;
     let mut cbs = RemoteCallbacks::new();
@@ -187,3 +655,247 @@ pub fn push(config: &Config, tag_name: &str) -> Result<(), Error> {
         .map(|_| ())
         .map_err(Error::from)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use crate::test_repo::TempRepo;
+
+    fn version(version: &str) -> Version {
+        Version::parse(version).unwrap()
+    }
+
+    #[test]
+    fn detached_head_has_no_branch() {
+        let repo = TempRepo::new();
+        let commit = repo.commit("chore: root").create();
+        assert!(current_branch(&repo).is_some());
+
+        repo.set_head_detached(commit).unwrap();
+        assert_eq!(current_branch(&repo), None);
+    }
+
+    #[test]
+    fn reverted_commits_cancel_out() {
+        let repo = TempRepo::new();
+
+        let root = repo.commit("chore: root").parents(&[]).create();
+        repo.tag_commit(root, "v1.0.0");
+        let feature = repo.commit("feat: Add X").parents(&[root]).create();
+        let fix = repo.commit("fix: Repair Y").parents(&[feature]).create();
+        let revert = repo
+            .commit(&format!(
+                "Revert \"feat: Add X\"\n\nThis reverts commit {}.",
+                feature
+            ))
+            .parents(&[fix])
+            .create();
+        let summaries = |repo: &Repository| {
+            commits_since(repo, Some("v1.0.0"), &CommitFilter::default())
+                .unwrap()
+                .iter()
+                .map(|c| c.summary().unwrap().to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        repo.set_head_detached(revert).unwrap();
+        assert_eq!(summaries(&repo), vec!["fix: Repair Y"]);
+
+        let reapply = repo
+            .commit("Revert \"Revert \"feat: Add X\"\"")
+            .parents(&[revert])
+            .create();
+        repo.set_head_detached(reapply).unwrap();
+        assert_eq!(summaries(&repo), vec!["fix: Repair Y", "feat: Add X"]);
+    }
+
+    #[test]
+    fn analysis_explains_skipped_commits() {
+        let repo = TempRepo::new();
+
+        let root = repo.commit("chore: root").parents(&[]).create();
+        repo.tag_commit(root, "v1.0.0");
+        let feature = repo.commit("feat: Add X").parents(&[root]).create();
+        let fix = repo.commit("fix: Repair Y").parents(&[feature]).create();
+        let revert = repo
+            .commit(&format!(
+                "Revert \"feat: Add X\"\n\nThis reverts commit {}.",
+                feature
+            ))
+            .parents(&[fix])
+            .create();
+        repo.set_head_detached(revert).unwrap();
+        let analyze = |paths: &[PathBuf]| {
+            let filter = CommitFilter {
+                paths: paths.to_vec(),
+                ..CommitFilter::default()
+            };
+            analyze_since(&repo, Some("v1.0.0"), &filter, &BumpRules::default())
+                .unwrap()
+                .into_iter()
+                .map(|record| (record.bump, record.skipped))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            analyze(&[]),
+            vec![
+                (
+                    CommitType::Unknown,
+                    Some(format!("reverts {}", &feature.to_string()[..8]))
+                ),
+                (CommitType::Patch, None),
+                (
+                    CommitType::Minor,
+                    Some(format!("reverted by {}", &revert.to_string()[..8]))
+                ),
+            ]
+        );
+        assert!(analyze(&[PathBuf::from("src")])
+            .iter()
+            .all(|(_, skipped)| skipped.as_deref() == Some("no changes in src")));
+        assert_eq!(
+            version_bump_since_tag(
+                &repo,
+                "v1.0.0",
+                &CommitFilter::default(),
+                &BumpRules::default()
+            ),
+            CommitType::Patch
+        );
+    }
+
+    #[test]
+    fn collects_contributors() {
+        let repo = TempRepo::new();
+        fs::write(
+            repo.path().join(".mailmap"),
+            "Jane Doe <semantic-rs@example.com> jdoe <semantic-rs@example.com>\n\
+             Max Mustermann <max@example.com>\n",
+        )
+        .unwrap();
+
+        repo.commit("feat: Add X")
+            .author("jdoe")
+            .files(&["a"])
+            .create();
+        repo.commit("chore: Bump Y")
+            .author("dependabot[bot]")
+            .files(&["b"])
+            .create();
+        repo.commit(
+            "fix: Repair Z\n\n\
+             Co-authored-by: Max <max@example.com>\n\
+             co-authored-by: github-actions[bot] <actions@github.com>\n\
+             Co-authored-by: Erika Mustermann <erika@example.com>",
+        )
+        .author("Jane Doe")
+        .files(&["c"])
+        .create();
+
+        let commits = commits_since(&repo, None, &CommitFilter::default()).unwrap();
+        assert_eq!(
+            contributors(&repo, &commits, &["*[bot]".into()]),
+            vec!["Jane Doe", "Max Mustermann", "Erika Mustermann"]
+        );
+        assert_eq!(
+            contributors(&repo, &commits, &["Jane*".into(), "*Mustermann".into()]),
+            vec!["dependabot[bot]", "github-actions[bot]"]
+        );
+    }
+
+    #[test]
+    fn filters_commits() {
+        let repo = TempRepo::new();
+        let filter = CommitFilter {
+            paths: vec![PathBuf::from("crates/foo")],
+            excluded_paths: vec![PathBuf::from("crates/foo/bar")],
+            ignored_authors: vec!["*[bot]".into()],
+            ignored_paths: vec![PathBuf::from("crates/foo/docs")],
+        };
+        let skip_reason = |author, message, files: &[&str]| {
+            let oid = repo.commit(message).author(author).files(files).create();
+            filter.skip_reason(&repo, &repo.find_commit(oid).unwrap())
+        };
+
+        assert_eq!(
+            skip_reason("jane", "chore: init", &["README.md"]),
+            Some("no changes in crates/foo".into())
+        );
+        assert_eq!(
+            skip_reason("jane", "fix: Code", &["crates/foo/src/lib.rs"]),
+            None
+        );
+        assert_eq!(
+            skip_reason("jane", "fix: Nested", &["crates/foo/bar/src/lib.rs"]),
+            Some("only changes in crates/foo/bar".into())
+        );
+        assert_eq!(
+            skip_reason("jane", "docs: Guide", &["crates/foo/docs/guide.md"]),
+            Some("only changes in crates/foo/docs".into())
+        );
+        assert_eq!(
+            skip_reason(
+                "jane",
+                "fix: Code and docs",
+                &["crates/foo/src/lib.rs", "crates/foo/docs/guide.md"]
+            ),
+            None
+        );
+        assert_eq!(
+            skip_reason(
+                "dependabot[bot]",
+                "chore(deps): Bump serde",
+                &["crates/foo/Cargo.toml"]
+            ),
+            Some("authored by dependabot[bot]".into())
+        );
+        assert_eq!(
+            skip_reason(
+                "jane",
+                "feat: Experiment [skip release]",
+                &["crates/foo/src/lib.rs"]
+            ),
+            Some("marked with [skip release]".into())
+        );
+    }
+
+    #[test]
+    fn latest_tag_is_reachable_from_head() {
+        let repo = TempRepo::new();
+        let format = TagFormat::new("v{version}").unwrap();
+        let latest = |first_parent| {
+            latest_tag(&repo, &format, "foo", first_parent, |_: &Version| true)
+                .map(|(version, _)| version)
+        };
+
+        // v1.0.0 -- v1.1.0 ------- merge   (master)
+        //       \                 /
+        //        v1.0.1 -- v2.0.0          (feature)
+        let root = repo.commit("feat: root").parents(&[]).create();
+        let minor = repo.commit("feat: minor").parents(&[root]).create();
+        let fix = repo.commit("fix: patch").parents(&[root]).create();
+        let major = repo.commit("feat: major").parents(&[fix]).create();
+        let merge = repo
+            .commit("Merge branch 'feature'")
+            .parents(&[minor, major])
+            .create();
+        repo.tag_commit(root, "v1.0.0");
+        repo.tag_commit(minor, "v1.1.0");
+        repo.tag_commit(fix, "v1.0.1");
+        repo.tag_commit(major, "v2.0.0");
+
+        repo.set_head_detached(minor).unwrap();
+        assert_eq!(latest(false), Some(version("1.1.0")));
+
+        repo.set_head_detached(fix).unwrap();
+        assert_eq!(latest(false), Some(version("1.0.1")));
+
+        repo.set_head_detached(merge).unwrap();
+        assert_eq!(latest(false), Some(version("2.0.0")));
+        assert_eq!(latest(true), Some(version("1.1.0")));
+    }
+}
@@ -25,10 +25,15 @@ pub fn is_github_url(url: &str) -> bool {
     url.contains("github.com")
 }
 
-pub fn release(config: &Config, tag_name: &str, tag_message: &str) -> Result<(), Error> {
+pub fn release(
+    config: &Config,
+    tag_name: &str,
+    tag_message: &str,
+    prerelease: bool,
+) -> Result<(), Error> {
     let user = &config.user.as_ref().unwrap()[..];
     let repo_name = &config.repository_name.as_ref().unwrap()[..];
-    let branch = &config.branch[..];
+    let branch = &config.branch.as_ref().unwrap()[..];
     let token = config.gh_token.as_ref().unwrap();
 
     let credentials = Credentials::Token(token.to_owned());
@@ -39,7 +44,7 @@ pub fn release(config: &Config, tag_name: &str, tag_message: &str) -> Result<(),
         .body(tag_message)
         .commitish(branch)
         .draft(false)
-        .prerelease(false)
+        .prerelease(prerelease)
         .build();
 
     let repo = github.repo(user, repo_name);
@@ -0,0 +1,127 @@
+use crate::commit_analyzer;
+use crate::conventional_commit::{ConventionalCommit, ParseError};
+
+/// Why a commit message does not follow the Conventional Commits specification,
+/// and how to fix it.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Problem {
+    pub problem: String,
+    pub suggestion: String,
+}
+
+/// Removes the comments git adds to a message being edited, as in a `commit-msg` hook.
+pub fn strip_comments(message: &str) -> String {
+    message
+        .lines()
+        .take_while(|line| !line.starts_with("# ------------------------ >8"))
+        .filter(|line| !line.starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_owned()
+}
+
+/// Checks a commit message. Merge commits written by git are fine, and so are
+/// git's `Revert "<header>"` messages if the reverted header is.
+pub fn check(message: &str) -> Option<Problem> {
+    let header = message.lines().next().unwrap_or("").trim();
+    if header.starts_with("Merge ") {
+        return None;
+    }
+    if let Some(reverted) = commit_analyzer::reverted_header(header) {
+        return check(reverted);
+    }
+
+    let error = match ConventionalCommit::parse(message) {
+        Ok(_) => {
+            let second_line = message.lines().nth(1);
+            if second_line.map_or(false, |line| !line.trim().is_empty()) {
+                return Some(Problem {
+                    problem: "the header is not followed by a blank line".into(),
+                    suggestion: "Separate the header from the body with a blank line".into(),
+                });
+            }
+            return None;
+        }
+        Err(error) => error,
+    };
+
+    let description = header
+        .find(':')
+        .map_or(header, |colon| header[colon + 1..].trim());
+    let suggestion = match error {
+        ParseError::Empty => "Write a header like `fix: Describe the change`".into(),
+        ParseError::MissingType => format!("Start the header with a type, e.g. `fix: {}`", header),
+        ParseError::InvalidType(_) => format!(
+            "Use a single word as type, like `feat: {}` or `fix: {}`",
+            description, description
+        ),
+        ParseError::InvalidScope => format!(
+            "Write the scope like `fix(parser): {}`, or leave it out",
+            description
+        ),
+        ParseError::MissingSpace => format!(
+            "Add a space after the colon: `{}`",
+            header.replacen(':', ": ", 1)
+        ),
+        ParseError::EmptyDescription => {
+            "Describe the change after the colon, e.g. `fix: Handle empty input`".into()
+        }
+    };
+
+    Some(Problem {
+        problem: error.to_string(),
+        suggestion,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_conventional_commits() {
+        assert_eq!(check("feat(lint): Add lint command\n\nWith a body."), None);
+        assert_eq!(check("Merge branch 'feature'"), None);
+    }
+
+    #[test]
+    fn accepts_reverts_of_conventional_commits() {
+        let revert = "Revert \"fix: small\"\n\nThis reverts commit 0123456789abcdef.";
+        assert_eq!(check(revert), None);
+        assert_eq!(check("Revert \"Revert \"feat: Add lint\"\""), None);
+        assert_eq!(
+            check("Revert \"Handle empty input\"").unwrap().suggestion,
+            "Start the header with a type, e.g. `fix: Handle empty input`"
+        );
+    }
+
+    #[test]
+    fn suggests_fixes() {
+        assert_eq!(
+            check("fix:Handle empty input").unwrap().suggestion,
+            "Add a space after the colon: `fix: Handle empty input`"
+        );
+        assert_eq!(
+            check("Handle empty input").unwrap().suggestion,
+            "Start the header with a type, e.g. `fix: Handle empty input`"
+        );
+        assert_eq!(
+            check("fix: Handle empty input\nIt crashed.")
+                .unwrap()
+                .problem,
+            "the header is not followed by a blank line"
+        );
+    }
+
+    #[test]
+    fn strips_git_comments() {
+        let message = "fix: Handle empty input
+
+# Please enter the commit message for your changes.
+# ------------------------ >8 ------------------------
+diff --git a/src/main.rs b/src/main.rs
+";
+        assert_eq!(strip_comments(message), "fix: Handle empty input");
+    }
+}
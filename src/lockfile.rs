@@ -0,0 +1,149 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use semver::Version;
+use toml_edit::Item;
+
+use crate::toml_file::{self, TomlError};
+
+/// A crate of the repository that gets a new version, as `(name, old version, new version)`.
+pub type Bump<'a> = (&'a str, &'a Version, &'a Version);
+
+fn find_bump<'a>(bumps: &'a [Bump], name: &str, version: &str) -> Option<&'a Bump<'a>> {
+    bumps
+        .iter()
+        .find(|(bumped, old, _)| *bumped == name && old.to_string() == version)
+}
+
+/// Rewrites a dependency on a bumped crate, like `"foo 1.0.0"`.
+///
+/// References to crates from a registry or git carry their source as well and are left alone.
+fn updated_dependency(dependency: &str, bumps: &[Bump]) -> Option<String> {
+    let mut parts = dependency.split(' ');
+    let (name, version) = (parts.next()?, parts.next()?);
+    if parts.next().is_some() {
+        return None;
+    }
+
+    find_bump(bumps, name, version).map(|(_, _, new)| format!("{} {}", name, new))
+}
+
+/// Sets the new versions of the bumped crates in a `Cargo.lock`, leaving
+/// every other package as it is resolved.
+pub fn file_with_new_versions(file: &str, bumps: &[Bump]) -> Result<String, TomlError> {
+    let mut document = toml_file::parse_document(file)?;
+    let packages = document
+        .get_mut("package")
+        .and_then(Item::as_array_of_tables_mut)
+        .ok_or(TomlError::Parse("No packages found in Cargo.lock"))?;
+
+    for package in packages.iter_mut() {
+        // Crates of the repository have no `source`.
+        let is_local = !package.contains_key("source");
+        let name = package.get("name").and_then(Item::as_str);
+        let version = package.get("version").and_then(Item::as_str);
+        let bump = match (name, version) {
+            (Some(name), Some(version)) if is_local => find_bump(bumps, name, version).copied(),
+            _ => None,
+        };
+        if let Some((_, _, new)) = bump {
+            if let Some(version) = package.get_mut("version").and_then(Item::as_value_mut) {
+                toml_file::replace_string(version, &new.to_string());
+            }
+        }
+
+        if let Some(dependencies) = package.get_mut("dependencies").and_then(Item::as_array_mut) {
+            for dependency in dependencies.iter_mut() {
+                if let Some(updated) = dependency
+                    .as_str()
+                    .and_then(|dependency| updated_dependency(dependency, bumps))
+                {
+                    toml_file::replace_string(dependency, &updated);
+                }
+            }
+        }
+    }
+
+    Ok(toml_file::render(&document, file))
+}
+
+/// Updates `Cargo.lock` in the repository root, if there is one.
+pub fn write_new_versions(repository_path: &str, bumps: &[Bump]) -> Result<(), TomlError> {
+    let file_path = Path::new(repository_path).join("Cargo.lock");
+    let lockfile = match fs::read_to_string(&file_path) {
+        Ok(lockfile) => lockfile,
+        Err(ref err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(TomlError::Io(err)),
+    };
+
+    let new_lockfile = file_with_new_versions(&lockfile, bumps)?;
+    fs::write(file_path, new_lockfile).map_err(TomlError::Io)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCKFILE: &str = r#"# This file is automatically @generated by Cargo.
+# It is not intended for manual editing.
+version = 3
+
+[[package]]
+name = "app"
+version = "0.3.0"
+dependencies = [
+ "core 1.0.0",
+ "serde",
+]
+
+[[package]]
+name = "core"
+version = "1.0.0"
+dependencies = [
+ "serde",
+]
+
+[[package]]
+name = "core"
+version = "1.0.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "0000000000000000000000000000000000000000000000000000000000000000"
+
+[[package]]
+name = "legacy"
+version = "0.1.0"
+dependencies = [
+ "core 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+]
+
+[[package]]
+name = "serde"
+version = "1.0.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#;
+
+    #[test]
+    fn updates_local_packages_only() {
+        let old = Version::parse("1.0.0").unwrap();
+        let new = Version::parse("1.1.0").unwrap();
+        let lockfile = file_with_new_versions(LOCKFILE, &[("core", &old, &new)]).unwrap();
+
+        let expected = LOCKFILE
+            .replacen(" \"core 1.0.0\",", " \"core 1.1.0\",", 1)
+            .replacen(
+                "name = \"core\"\nversion = \"1.0.0\"\ndependencies",
+                "name = \"core\"\nversion = \"1.1.0\"\ndependencies",
+                1,
+            );
+        assert_eq!(lockfile, expected);
+    }
+
+    #[test]
+    fn leaves_other_versions_alone() {
+        let old = Version::parse("0.2.0").unwrap();
+        let new = Version::parse("0.3.0").unwrap();
+        let lockfile = file_with_new_versions(LOCKFILE, &[("app", &old, &new)]).unwrap();
+        assert_eq!(lockfile, LOCKFILE);
+    }
+}
@@ -1,41 +1,64 @@
+extern crate chrono;
 extern crate clap;
-extern crate clog;
 extern crate env_logger;
 extern crate git2;
+extern crate handlebars;
 extern crate hubcaps;
 #[macro_use]
 extern crate log;
 extern crate regex;
 extern crate semver;
+extern crate serde;
+extern crate serde_json;
 extern crate tokio;
 extern crate toml;
+extern crate toml_edit;
 extern crate url;
 
-use std::io::Write;
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
 use std::process::exit;
 use std::thread;
 use std::time::Duration;
 use std::{env, fs};
 
-use clap::{App, Arg, ArgMatches};
+use clap::{App, Arg, ArgMatches, SubCommand};
 use env_logger::{fmt::Color, Builder, Env};
 use semver::Version;
 
-use crate::commit_analyzer::CommitType;
+use crate::commit_analyzer::BumpRules;
 use crate::config::ConfigBuilder;
-use crate::utils::user_repo_from_url;
+use crate::config_file::BranchConfig;
+use crate::tag_format::TagFormat;
+use crate::utils::{host_from_url, user_repo_from_url};
+use crate::version::{InitialVersion, ReleaseHistory};
+use crate::version_file::VersionFile;
+use crate::workspace::{Package, Release};
 
 mod cargo;
 mod changelog;
 mod commit_analyzer;
 mod config;
+mod config_file;
+mod conventional_commit;
 mod error;
+mod explain;
 mod git;
 mod github;
+mod lint;
+mod lockfile;
+mod manifest;
+mod planner;
 mod preflight;
+mod tag_format;
+#[cfg(test)]
+mod test_repo;
 mod toml_file;
 mod utils;
+mod version;
+mod version_file;
+mod workspace;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const USERAGENT: &str = concat!("semantic-rs/", env!("CARGO_PKG_VERSION"));
@@ -81,98 +104,74 @@ fn string_to_bool(answer: &str) -> bool {
     matches!(&answer.to_lowercase()[..], "yes" | "true" | "1")
 }
 
-fn version_bump(version: &Version, bump: CommitType) -> Option<Version> {
-    let mut version = version.clone();
-
-    // NB: According to the Semver spec, major version zero is for
-    // the initial development phase is treated slightly differently.
-    // The minor version is incremented for breaking changes
-    // and major is kept at zero until the public API has become more stable.
-    if version.major == 0 {
-        match bump {
-            CommitType::Unknown => return None,
-            CommitType::Patch => version.increment_patch(),
-            CommitType::Minor => version.increment_patch(),
-            CommitType::Major => version.increment_minor(),
-        }
-    } else {
-        match bump {
-            CommitType::Unknown => return None,
-            CommitType::Patch => version.increment_patch(),
-            CommitType::Minor => version.increment_minor(),
-            CommitType::Major => version.increment_major(),
-        }
-    }
-
-    Some(version)
-}
-
-#[test]
-fn test_breaking_bump_major_zero() {
-    let buggy_release = Version::parse("0.2.0").unwrap();
-    let bumped_version = version_bump(&buggy_release, CommitType::Major).unwrap();
-    assert_eq!(bumped_version, Version::parse("0.3.0").unwrap());
-}
-
-#[test]
-fn test_breaking_bump_major_one() {
-    let buggy_release = Version::parse("1.0.0").unwrap();
-    let bumped_version = version_bump(&buggy_release, CommitType::Major).unwrap();
-    assert_eq!(bumped_version, Version::parse("2.0.0").unwrap());
-}
-
 fn ci_env_set() -> bool {
     env::var("CI").is_ok()
 }
 
-fn current_branch(repo: &git2::Repository) -> Option<String> {
-    let head = repo.head().expect("No HEAD found for repository");
-
-    if head.is_branch() {
-        let short = head.shorthand().expect("No branch name found");
-        return Some(short.into());
-    }
-
-    None
-}
-
-fn is_release_branch(current: &str, release: &str) -> bool {
-    current == release
+fn is_release_branch(current: &str, branches: &BTreeMap<String, BranchConfig>) -> bool {
+    branches.contains_key(current)
 }
 
-fn push_to_github(config: &config::Config, tag_name: &str) {
-    info!("Pushing new commit and tag");
-    git::push(&config, &tag_name)
-        .unwrap_or_else(|err| error_exit!("Failed to push git: {:?}", err));
+fn push_to_github(config: &config::Config, tag_names: &[String]) {
+    info!("Pushing new commit and tags");
+    git::push(config, tag_names).unwrap_or_else(|err| error_exit!("Failed to push git: {:?}", err));
 
-    info!("Waiting a tiny bit, so GitHub can store the git tag");
+    info!("Waiting a tiny bit, so GitHub can store the git tags");
     thread::sleep(Duration::from_secs(1));
 }
 
-fn release_on_github(config: &config::Config, tag_message: &str, tag_name: &str) {
-    if github::can_release(&config) {
-        info!("Creating GitHub release");
-        github::release(&config, &tag_name, &tag_message)
+fn release_on_github(config: &config::Config, release: &Release, tag_message: &str) {
+    if github::can_release(config) {
+        let tag_name = release.tag_name();
+        let prerelease = release.new_version.is_prerelease();
+        info!("Creating GitHub release for {}", tag_name);
+        github::release(config, &tag_name, tag_message, prerelease)
             .unwrap_or_else(|err| error_exit!("Failed to create GitHub release: {:?}", err));
     } else {
         info!("Project not hosted on GitHub. Skipping release step");
     }
 }
 
-fn release_on_cratesio(config: &config::Config) {
-    info!("Publishing crate on crates.io");
+fn release_on_cratesio(config: &config::Config, release: &Release) {
+    info!("Publishing {} on crates.io", release.package.name);
     if !cargo::publish(
-        &config.repository_path,
+        &release.package.directory(&config.repository_path),
         &config.cargo_token.as_ref().unwrap(),
     ) {
         error_exit!("Failed to publish on crates.io");
     }
 }
 
-fn generate_changelog(repository_path: &str, version: &Version, new_version: &str) -> String {
-    info!("New version would be: {}", new_version);
+/// Commits that count towards a crate's release.
+///
+/// A crate at the repository root sees every commit, others only those
+/// changing their own or shared paths.
+fn commit_filter(config: &config::Config, package: &Package) -> git::CommitFilter {
+    let paths = if package.path.as_os_str().is_empty() {
+        vec![]
+    } else {
+        let mut paths = vec![package.path.clone()];
+        paths.extend(config.shared_paths.iter().cloned());
+        paths
+    };
+
+    git::CommitFilter {
+        paths,
+        excluded_paths: package.nested_paths.clone(),
+        ignored_authors: config.ignored_authors.clone(),
+        ignored_paths: config.ignored_paths.clone(),
+    }
+}
+
+fn generate_changelog(config: &config::Config, release: &Release) -> String {
+    info!("New version would be: {}", release.label());
     info!("Would write the following Changelog:");
-    match changelog::generate(repository_path, &version.to_string(), new_version) {
+    match changelog::generate(
+        &config.repository,
+        &config.changelog,
+        release,
+        &commit_filter(config, &release.package),
+    ) {
         Ok(_log) => _log,
         Err(err) => {
             error_exit!("Generating Changelog failed: {:?}", err);
@@ -180,10 +179,121 @@ fn generate_changelog(repository_path: &str, version: &Version, new_version: &st
     }
 }
 
-fn write_changelog(repository_path: &str, version: &Version, new_version: &str) {
-    info!("Writing Changelog");
-    changelog::write(repository_path, &version.to_string(), &new_version)
-        .unwrap_or_else(|err| error!("Writing Changelog failed: {:?}", err));
+/// Writes the changelog file of a release and returns its path relative to
+/// the repository root, to commit it.
+fn write_changelog(config: &config::Config, release: &Release) -> Option<PathBuf> {
+    let directory = Path::new(&config.repository_path).join(&release.package.path);
+    let file = match config.changelog.file(&directory) {
+        Some(file) => file,
+        None => {
+            info!("No changelog file is written, only release notes");
+            return None;
+        }
+    };
+
+    info!("Writing {}", release.package.path.join(&file).display());
+    match changelog::write(
+        &config.repository,
+        &config.changelog,
+        &directory.join(&file),
+        release,
+        &commit_filter(config, &release.package),
+    ) {
+        Ok(()) => Some(release.package.path.join(file)),
+        Err(err) => {
+            error!("Writing Changelog failed: {:?}", err);
+            None
+        }
+    }
+}
+
+/// Rebuilds the changelogs of all crates from their release tags and writes
+/// them, or prints them without `write`.
+fn regenerate_changelogs(config: &config::Config, write: bool) -> ! {
+    for package in read_packages(config) {
+        let changelog = changelog::regenerate(
+            &config.repository,
+            &config.changelog,
+            &package,
+            &commit_filter(config, &package),
+        )
+        .unwrap_or_else(|err| {
+            error_exit!("Generating Changelog failed: {:?}", err);
+        });
+
+        if changelog.is_empty() {
+            info!("No releases of {} tagged yet", package.name);
+        } else if write {
+            let directory = Path::new(&config.repository_path).join(&package.path);
+            let file = config.changelog.file(&directory).unwrap_or_else(|| {
+                error_exit!("No changelog file is written, it is turned off in the configuration");
+            });
+            info!("Writing {}", package.path.join(&file).display());
+            changelog::replace(&directory.join(file), &changelog).unwrap_or_else(|err| {
+                error_exit!("Writing Changelog failed: {:?}", err);
+            });
+        } else {
+            print!("{}", changelog);
+        }
+    }
+
+    exit(0);
+}
+
+fn read_packages(config: &config::Config) -> Vec<Package> {
+    let packages = workspace::packages(&config.repository_path, config.tag_format.as_ref())
+        .unwrap_or_else(|err| {
+            error_exit!("Reading `Cargo.toml` failed: {}", err);
+        });
+
+    if packages.len() > 1 && packages.iter().any(|p| !p.tag_format.contains_crate()) {
+        error_exit!("The tag format needs to contain {{crate}} to release several crates");
+    }
+
+    packages
+}
+
+fn release_manifests(
+    config: &config::Config,
+    releases: &[Release],
+    commit: Option<String>,
+) -> Vec<manifest::ReleaseManifest> {
+    releases
+        .iter()
+        .map(|release| {
+            manifest::describe(
+                &config.repository,
+                release,
+                &commit_filter(config, &release.package),
+                commit.clone(),
+            )
+            .unwrap_or_else(|err| {
+                error_exit!("Describing the release failed: {}", err);
+            })
+        })
+        .collect()
+}
+
+/// Whether the manifest is inside the working tree and not ignored, so it is
+/// committed on top of the release commit it names.
+fn manifest_in_commit(config: &config::Config, path: &Path) -> bool {
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        && !config.repository.status_should_ignore(path).unwrap_or(true)
+}
+
+fn write_manifest(
+    config: &config::Config,
+    path: &Path,
+    releases: &[Release],
+    commit: Option<String>,
+) {
+    info!("Writing release manifest {}", path.display());
+    manifest::write(
+        &Path::new(&config.repository_path).join(path),
+        &release_manifests(config, releases, commit),
+    )
+    .unwrap_or_else(|err| error!("Writing the release manifest failed: {}", err));
 }
 
 fn print_changelog(changelog: &str) {
@@ -193,20 +303,38 @@ fn print_changelog(changelog: &str) {
     info!("Would create annotated git tag");
 }
 
-fn package_crate(config: &config::Config, repository_path: &str, new_version: &str) {
-    if config.release_mode {
-        info!("Updating lockfile");
-        if !cargo::update_lockfile(repository_path) {
-            error!("`cargo fetch` failed. See above for the cargo error message.");
-        }
+fn package_crates(config: &config::Config, releases: &[Release], updated_files: &[PathBuf]) {
+    let repository_path = &config.repository_path;
+
+    info!("Updating lockfile");
+    let bumps = releases
+        .iter()
+        .map(|release| {
+            (
+                &release.package.name[..],
+                &release.version,
+                &release.new_version,
+            )
+        })
+        .collect::<Vec<_>>();
+    lockfile::write_new_versions(repository_path, &bumps)
+        .unwrap_or_else(|err| error!("Updating `Cargo.lock` failed: {}", err));
+
+    let mut files = vec![PathBuf::from("Cargo.lock")];
+    for release in releases {
+        files.push(release.package.version_location.manifest());
     }
+    files.extend(updated_files.iter().cloned());
+    let new_versions = releases.iter().map(Release::label).collect::<Vec<_>>();
 
-    git::commit_files(&config, &new_version)
+    git::commit_files(config, &files, &new_versions)
         .unwrap_or_else(|err| error!("Committing files failed: {:?}", err));
 
-    info!("Package crate");
-    if !cargo::package(repository_path) {
-        error!("`cargo package` failed. See above for the cargo error message.");
+    for release in releases {
+        info!("Package crate {}", release.package.name);
+        if !cargo::package(&release.package.directory(repository_path)) {
+            error!("`cargo package` failed. See above for the cargo error message.");
+        }
     }
 }
 
@@ -246,7 +374,8 @@ fn get_signature<'a>(repository_path: String) -> git2::Signature<'a> {
     signature.to_owned()
 }
 
-fn get_user_and_repo(repository_path: &str) -> Option<(String, String)> {
+/// Host, user and name of the `origin` repository.
+fn get_remote_repository(repository_path: &str) -> Option<(String, String, String)> {
     let repo = get_repo(repository_path);
     let remote_or_none = repo.find_remote("origin");
     match remote_or_none {
@@ -262,7 +391,9 @@ fn get_user_and_repo(repository_path: &str) -> Option<(String, String)> {
                 );
             });
 
-            Some((user, repo_name))
+            let host = host_from_url(&url).unwrap_or_else(|| "github.com".into());
+
+            Some((host, user, repo_name))
         }
         Err(err) => {
             warn!("Could not determine the origin remote url: {:?}", err);
@@ -295,13 +426,86 @@ fn get_cargo_token() -> Option<String> {
     env::var("CARGO_TOKEN").ok()
 }
 
-fn assemble_configuration(args: ArgMatches) -> config::Config {
+fn version_files(configs: &[config_file::VersionFileConfig]) -> Vec<VersionFile> {
+    configs
+        .iter()
+        .map(|config| {
+            let path = PathBuf::from(&config.path);
+            let crate_name = config.crate_name.clone();
+            let file = match (&config.pattern, &config.regex) {
+                (Some(pattern), None) => VersionFile::with_placeholders(path, crate_name, pattern),
+                (None, Some(regex)) => VersionFile::with_regex(path, crate_name, regex),
+                _ => Err(format!(
+                    "Version file {} needs either a pattern or a regex",
+                    config.path
+                )),
+            };
+            file.unwrap_or_else(|err| error_exit!("{}", err))
+        })
+        .collect()
+}
+
+fn changelog_settings(
+    repository_path: &str,
+    config: &config_file::ChangelogConfig,
+) -> changelog::Settings {
+    let template = config.template.as_ref().map(|template| {
+        fs::read_to_string(Path::new(repository_path).join(template)).unwrap_or_else(|err| {
+            error_exit!(
+                "Reading the changelog template {} failed: {}",
+                template,
+                err
+            );
+        })
+    });
+    let sections = config.sections.as_ref().map(|sections| {
+        sections
+            .iter()
+            .map(|section| changelog::SectionSettings {
+                title: section.title.clone(),
+                types: section
+                    .types
+                    .iter()
+                    .map(|commit_type| commit_type.to_ascii_lowercase())
+                    .collect(),
+                breaking: section.breaking,
+            })
+            .collect()
+    });
+    let style = match config.style {
+        Some(ref style) => style.parse().unwrap_or_else(|err| error_exit!("{}", err)),
+        None => changelog::Style::Clog,
+    };
+
+    let mut settings =
+        changelog::Settings::new(style, template, sections, config.hidden_types.clone())
+            .unwrap_or_else(|err| error_exit!("{}", err));
+    if let Some(ref commit) = config.links.commit {
+        settings.links.commit = commit.clone();
+    }
+    if let Some(ref compare) = config.links.compare {
+        settings.links.compare = compare.clone();
+    }
+    if let Some(ref issue) = config.links.issue {
+        settings.links.issue = issue.clone();
+    }
+    if let Some(ref excluded) = config.exclude_contributors {
+        settings.excluded_contributors = excluded.clone();
+    }
+    settings.path = config.path.as_ref().map(PathBuf::from);
+    settings.write_file = config.file.unwrap_or(true);
+    settings
+}
+
+fn assemble_configuration(args: ArgMatches, explain: bool) -> config::Config {
     let mut config_builder = ConfigBuilder::new();
 
     // If write mode is requested OR denied,
     // adhere to the user's wish,
     // otherwise we decide based on whether we are running in CI.
+    // Explaining never writes anything.
     let write_mode = match args.value_of("write") {
+        _ if explain => false,
         Some(write_mode) => string_to_bool(write_mode),
         None => ci_env_set(),
     };
@@ -314,20 +518,95 @@ fn assemble_configuration(args: ArgMatches) -> config::Config {
     // We can only release, if we are allowed to write
     let release_mode = write_mode && release_flag;
     let repository_path = get_repository_path(&args);
+    let config_file = config_file::read(&repository_path).unwrap_or_else(|err| {
+        error_exit!("Reading `{}` failed: {}", config_file::CONFIG_FILE, err);
+    });
 
     config_builder.write(write_mode);
     config_builder.release(release_mode);
-    config_builder.branch(args.value_of("branch").unwrap_or("master").to_string());
+    // A branch given on the command line replaces the configured ones.
+    let branches = match args.value_of("branch") {
+        Some(branch) => {
+            let branch_config = config_file.branches.get(branch).cloned();
+            vec![(branch.to_string(), branch_config.unwrap_or_default())]
+                .into_iter()
+                .collect()
+        }
+        None if !config_file.branches.is_empty() => config_file.branches.clone(),
+        None => vec![("master".to_string(), BranchConfig::default())]
+            .into_iter()
+            .collect(),
+    };
+    config_builder.branches(branches);
+
+    // Explaining also works on a detached HEAD, like a pull request checked out in CI.
+    match git::current_branch(&get_repo(&repository_path)) {
+        Some(branch) => {
+            config_builder.branch(branch);
+        }
+        None if explain => {}
+        None => error_exit!("Could not determine current branch."),
+    }
     config_builder.repository_path(repository_path.clone());
-    config_builder.signature(get_signature(repository_path.clone()));
-    if let Some((user, repo)) = get_user_and_repo(&repository_path) {
+    config_builder.shared_paths(config_file.shared_paths.iter().map(PathBuf::from).collect());
+    config_builder.ignored_authors(config_file.ignore_authors.clone());
+    config_builder.ignored_paths(config_file.ignore_paths.iter().map(PathBuf::from).collect());
+    if let Some(ref manifest) = config_file.manifest {
+        config_builder.manifest(PathBuf::from(manifest));
+    }
+    config_builder.version_files(version_files(&config_file.version_files));
+    if let Some(level) = args.value_of("bump") {
+        config_builder.bump(level.parse().unwrap_or_else(|err| error_exit!("{}", err)));
+    }
+    let bump_rules = BumpRules::new(&config_file.bump_rules, config_file.default_bump.as_deref())
+        .unwrap_or_else(|err| {
+            error_exit!(
+                "Invalid bump rules in `{}`: {}",
+                config_file::CONFIG_FILE,
+                err
+            );
+        });
+    config_builder.bump_rules(bump_rules);
+    if let Some(version) = args.value_of("release-as") {
+        let version = Version::parse(version).unwrap_or_else(|err| {
+            error_exit!(
+                "Invalid version '{}' given with --release-as: {}",
+                version,
+                err
+            );
+        });
+        config_builder.release_as(version);
+    }
+    if let Some(name) = args.value_of("package") {
+        config_builder.bump_package(name.to_owned());
+    }
+    if let Some(ref initial) = config_file.initial_version {
+        let initial = InitialVersion::parse(initial).unwrap_or_else(|err| error_exit!("{}", err));
+        config_builder.initial_version(initial);
+    }
+    config_builder.first_parent(args.is_present("first-parent") || config_file.first_parent);
+    // Only commits and tags need a committer.
+    if write_mode {
+        config_builder.signature(get_signature(repository_path.clone()));
+    }
+    let mut changelog = changelog_settings(&repository_path, &config_file.changelog);
+    if let Some((host, user, repo)) = get_remote_repository(&repository_path) {
+        changelog.links.repository = Some(format!("https://{}/{}/{}", host, user, repo));
         config_builder.user(user);
         config_builder.repository_name(repo);
     }
+    config_builder.changelog(changelog);
     if let (Some(gh_username), Some(gh_token)) = get_github_creds(&repository_path) {
         config_builder.gh_username(gh_username);
         config_builder.gh_token(gh_token);
     }
+    if let Some(format) = args
+        .value_of("tag-format")
+        .or(config_file.tag_format.as_deref())
+    {
+        let tag_format = TagFormat::new(format).unwrap_or_else(|err| error_exit!("{}", err));
+        config_builder.tag_format(tag_format);
+    }
     if let Some(cargo_token) = get_cargo_token() {
         config_builder.cargo_token(cargo_token);
     }
@@ -365,6 +644,53 @@ fn init_logger() {
         .init();
 }
 
+fn report_lint_problem(commit: &str, message: &str, problem: &lint::Problem) {
+    let header = message.lines().next().unwrap_or("");
+    error!("{}\"{}\": {}", commit, header, problem.problem);
+    info!("  {}", problem.suggestion);
+}
+
+fn lint(args: &ArgMatches, lint_args: &ArgMatches) -> ! {
+    let mut problems = 0;
+
+    if lint_args.is_present("stdin") {
+        let mut message = String::new();
+        io::stdin()
+            .read_to_string(&mut message)
+            .unwrap_or_else(|err| error_exit!("Reading the commit message failed: {}", err));
+        let message = lint::strip_comments(&message);
+
+        if let Some(problem) = lint::check(&message) {
+            report_lint_problem("", &message, &problem);
+            problems += 1;
+        }
+    } else {
+        let repo = get_repo(&get_repository_path(args));
+        let range = lint_args.value_of("range").expect("Range is required");
+        let commits = git::commits_in_range(&repo, range).unwrap_or_else(|err| {
+            error_exit!("Reading the commits in {} failed: {}", range, err);
+        });
+
+        // Merge commits are written by git or the code review tool.
+        for commit in commits.iter().filter(|commit| commit.parent_count() < 2) {
+            let message = commit.message().unwrap_or("");
+            if let Some(problem) = lint::check(message) {
+                let sha = commit.id().to_string();
+                report_lint_problem(&format!("{} ", &sha[..8]), message, &problem);
+                problems += 1;
+            }
+        }
+    }
+
+    if problems > 0 {
+        error_exit!(
+            "{} commit message(s) do not follow the Conventional Commits specification",
+            problems
+        );
+    }
+    info_exit!("All commit messages follow the Conventional Commits specification");
+}
+
 fn main() {
     init_logger();
     info!("semantic.rs 🚀");
@@ -388,108 +714,575 @@ fn main() {
         .arg(Arg::with_name("branch")
              .short("b")
              .long("branch")
-             .help("The branch on which releases should happen. [default: master].")
+             .help("The branch on which releases should happen. [default: master, or the branches in .semantic-rs.toml].")
              .value_name("BRANCH")
              .takes_value(true))
+        .arg(Arg::with_name("tag-format")
+             .long("tag-format")
+             .help("Template for tag names, using {crate} and {version}. [default: v{version}, or {crate}-v{version} in a workspace].")
+             .value_name("FORMAT")
+             .takes_value(true))
+        .arg(Arg::with_name("bump")
+             .long("bump")
+             .help("Force the version bump instead of deriving it from the commits.")
+             .value_name("LEVEL")
+             .possible_values(&["major", "minor", "patch"])
+             .takes_value(true))
+        .arg(Arg::with_name("release-as")
+             .long("release-as")
+             .help("Release exactly this version. It must be greater than the current version.")
+             .value_name("VERSION")
+             .takes_value(true))
+        .arg(Arg::with_name("package")
+             .long("package")
+             .help("The crate --bump and --release-as apply to, needed in a workspace.")
+             .value_name("NAME")
+             .takes_value(true))
+        .arg(Arg::with_name("first-parent")
+             .long("first-parent")
+             .help("Only look for the previous release on the first-parent history of HEAD."))
         .arg(Arg::with_name("path")
              .short("p")
              .long("path")
              .help("Specifies the repository path. [default: .]")
              .value_name("PATH")
              .takes_value(true))
+        .subcommand(SubCommand::with_name("lint")
+             .about("Checks that commit messages follow the Conventional Commits specification")
+             .arg(Arg::with_name("range")
+                  .help("Commits to check, like origin/master..HEAD")
+                  .value_name("RANGE")
+                  .required_unless("stdin"))
+             .arg(Arg::with_name("stdin")
+                  .long("stdin")
+                  .help("Check a single commit message read from stdin, e.g. in a commit-msg hook.")))
+        .subcommand(SubCommand::with_name("explain")
+             .about("Shows how each commit since the previous release was classified")
+             .arg(Arg::with_name("format")
+                  .long("format")
+                  .help("Output format")
+                  .value_name("FORMAT")
+                  .possible_values(&["table", "json"])
+                  .default_value("table")))
+        .subcommand(SubCommand::with_name("changelog")
+             .about("Rebuilds the changelog from all release tags and prints it")
+             .arg(Arg::with_name("regenerate")
+                  .long("regenerate")
+                  .help("Replace the changelog file instead of printing it")))
         .get_matches();
 
-    let config = assemble_configuration(clap_args);
+    if let Some(lint_args) = clap_args.subcommand_matches("lint") {
+        lint(&clap_args, lint_args);
+    }
+
+    let explain_format = clap_args
+        .subcommand_matches("explain")
+        .and_then(|args| args.value_of("format"))
+        .map(str::to_owned);
 
-    let branch = current_branch(&config.repository).unwrap_or_else(|| {
-        error_exit!("Could not determine current branch.");
-    });
+    let regenerate = clap_args
+        .subcommand_matches("changelog")
+        .map(|args| args.is_present("regenerate"));
 
-    if !is_release_branch(&branch, &config.branch) {
-        info!(
-            "Current branch is '{}', releases are only done from branch '{}'",
-            branch, config.branch
-        );
+    let config = assemble_configuration(clap_args, explain_format.is_some());
+    if let Some(write) = regenerate {
+        regenerate_changelogs(&config, write);
+    }
+    // Only explaining goes without a branch, on a detached HEAD.
+    let branch = config.branch.as_deref().unwrap_or_default();
+
+    if explain_format.is_none() && !is_release_branch(branch, &config.branches) {
+        let branches = config
+            .branches
+            .keys()
+            .map(|name| format!("'{}'", name))
+            .collect::<Vec<_>>();
+        if branches.len() == 1 {
+            info!(
+                "Current branch is '{}', releases are only done from branch {}",
+                branch, branches[0]
+            );
+        } else {
+            info!(
+                "Current branch is '{}', releases are only done from branches {}",
+                branch,
+                branches.join(", ")
+            );
+        }
         info_exit!("No release done from a pull request either.");
     }
 
+    let head_message = config
+        .repository
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .map(|commit| commit.message().unwrap_or("").to_owned())
+        .unwrap_or_default();
+    if explain_format.is_none() && commit_analyzer::skips_release(&head_message) {
+        info_exit!("The latest commit is marked with [skip release]. Nothing to do.");
+    }
+
+    // Explaining works on any branch, with the settings of a release branch
+    // named like it.
+    let branch_config = config_file::branch_config(&config.branches, config.branch.as_deref());
+    let channel = branch_config.prerelease.clone();
+    if let Some(ref channel) = channel {
+        info!("Releasing on pre-release channel '{}'", channel);
+    }
+
+    let range = branch_config
+        .range(config.branch.as_deref())
+        .unwrap_or_else(|err| {
+            error_exit!("Invalid range for branch '{}': {}", branch, err);
+        });
+    if let Some(ref range) = range {
+        info!("Maintenance branch, releasing versions {} only", range);
+    }
+    let in_range = |version: &Version| range.as_ref().map_or(true, |r| r.contains(version));
+    let is_stable = |version: &Version| in_range(version) && !version.is_prerelease();
+
     //Before we actually start, we do perform some preflight checks
     //Here we check if everything is in place to do a GitHub release and a
     //release on crates.io.
     //The important bit is, if something's missing, we do not abort since the user can still do all
     //other things except publishing
 
-    info!("Performing preflight checks now");
-    let warnings = preflight::check(&config);
+    if explain_format.is_none() {
+        info!("Performing preflight checks now");
+        let warnings = preflight::check(&config);
+
+        if warnings.is_empty() {
+            info!("Checks done. Everything is ok");
+        }
 
-    if warnings.is_empty() {
-        info!("Checks done. Everything is ok");
+        for warning in warnings {
+            warn!("{}", warning);
+        }
     }
 
-    for warning in warnings {
-        warn!("{}", warning);
+    let packages = read_packages(&config);
+    match config.bump_package {
+        Some(ref name) if !packages.iter().any(|package| package.name == *name) => {
+            error_exit!(
+                "There is no crate {} to apply --bump or --release-as to",
+                name
+            );
+        }
+        None if packages.len() > 1 && (config.bump.is_some() || config.release_as.is_some()) => {
+            error_exit!(
+                "--bump and --release-as apply to a single crate, choose it with --package"
+            );
+        }
+        _ => {}
+    }
+    for file in &config.version_files {
+        match file.crate_name {
+            Some(ref name) if !packages.iter().any(|package| package.name == *name) => {
+                error_exit!(
+                    "Version file {}: there is no crate {}",
+                    file.path.display(),
+                    name
+                );
+            }
+            None if packages.len() > 1 => {
+                error_exit!(
+                    "Version file {} needs a crate, there are several in the workspace",
+                    file.path.display()
+                );
+            }
+            _ => {}
+        }
     }
 
-    let version = toml_file::read_from_file(&config.repository_path).unwrap_or_else(|err| {
-        error_exit!("Reading `Cargo.toml` failed: {:?}", err);
-    });
+    let base_reason = format!(
+        "the highest {}version tagged on a commit reachable from {}{}",
+        if channel.is_some() { "" } else { "stable " },
+        if config.first_parent {
+            "the first-parent history of HEAD"
+        } else {
+            "HEAD"
+        },
+        range
+            .as_ref()
+            .map_or(String::new(), |range| format!(", within {}", range))
+    );
+
+    let mut crates = vec![];
+    let mut explanations = vec![];
+    for package in packages {
+        let directory = package.directory(&config.repository_path);
+        let version = toml_file::read_from_file(&config.repository_path, &package.version_location)
+            .unwrap_or_else(|err| {
+                error_exit!("Reading `Cargo.toml` of {} failed: {}", package.name, err);
+            });
 
-    let version = Version::parse(&version).expect("Not a valid version");
-    info!("Current version: {}", version.to_string());
+        let version = Version::parse(&version).expect("Not a valid version");
+        info!("Current version of {}: {}", package.name, version);
 
-    info!("Analyzing commits");
+        info!("Analyzing commits");
 
-    let bump = git::version_bump_since_latest(&config.repository);
-    if config.write_mode {
-        info!("Commits analyzed. Bump will be {:?}", bump);
-    } else {
-        info!("Commits analyzed. Bump would be {:?}", bump);
+        let filter = commit_filter(&config, &package);
+
+        // Stable releases are based on the latest stable release, pre-releases
+        // only need something new since the latest release of any kind.
+        let previous = git::latest_tag(
+            &config.repository,
+            &package.tag_format,
+            &package.name,
+            config.first_parent,
+            |version: &Version| {
+                in_range(version) && (channel.is_some() || !version.is_prerelease())
+            },
+        );
+        let latest_stable = if channel.is_some() {
+            git::latest_tag(
+                &config.repository,
+                &package.tag_format,
+                &package.name,
+                config.first_parent,
+                is_stable,
+            )
+        } else {
+            previous.clone()
+        };
+        if previous.is_none() {
+            info!("No previous release of {} found", package.name);
+        }
+
+        let previous_tag = previous.map(|(_, tag)| tag);
+        let bump = git::version_bump_since(
+            &config.repository,
+            previous_tag.as_deref(),
+            &filter,
+            &config.bump_rules,
+        );
+        let bump_since_stable = if channel.is_some() {
+            git::version_bump_since(
+                &config.repository,
+                latest_stable.as_ref().map(|(_, tag)| tag.as_str()),
+                &filter,
+                &config.bump_rules,
+            )
+        } else {
+            bump
+        };
+        let history = ReleaseHistory {
+            latest_stable: latest_stable.map(|(version, _)| version),
+            bump_since_stable,
+            versions: git::tagged_versions(&config.repository, &package.tag_format, &package.name)
+                .into_iter()
+                .map(|(version, _)| version)
+                .collect(),
+        };
+        if config.write_mode {
+            info!("Commits analyzed. Bump will be {:?}", bump);
+        } else {
+            info!("Commits analyzed. Bump would be {:?}", bump);
+        }
+
+        let overridden = config
+            .bump_package
+            .as_ref()
+            .map_or(true, |name| *name == package.name);
+        let bump = match config.bump.filter(|_| overridden) {
+            Some(forced) => {
+                info!("Bump of {} forced to {:?}", package.name, forced);
+                forced
+            }
+            None => bump,
+        };
+        let release_as = config
+            .release_as
+            .clone()
+            .filter(|_| overridden)
+            .or_else(|| {
+                git::release_as_since(&config.repository, previous_tag.as_deref(), &filter)
+            });
+        if let Some(ref release_as) = release_as {
+            if *release_as <= version {
+                error_exit!(
+                    "Cannot release {} as {}, it is not greater than the current version {}",
+                    package.name,
+                    release_as,
+                    version
+                );
+            }
+            info!(
+                "Releasing {} as requested version {}",
+                package.name, release_as
+            );
+        }
+        let release_as = release_as.or_else(|| {
+            let initial = config
+                .initial_version
+                .as_ref()
+                .filter(|_| previous_tag.is_none())?;
+            let initial = initial.resolve(&version);
+            info!(
+                "Releasing {} for the first time as {}",
+                package.name, initial
+            );
+            Some(initial)
+        });
+
+        if explain_format.is_some() {
+            let commits = git::analyze_since(
+                &config.repository,
+                previous_tag.as_deref(),
+                &filter,
+                &config.bump_rules,
+            )
+            .unwrap_or_else(|err| {
+                error_exit!("Reading the commits of {} failed: {}", package.name, err);
+            });
+            explanations.push(explain::Explanation {
+                name: package.name.clone(),
+                version: version.to_string(),
+                base_tag: previous_tag.clone(),
+                base_reason: match previous_tag {
+                    Some(_) => base_reason.clone(),
+                    None if config.initial_version.is_some() => {
+                        "no previous release, the first release has the initial version".into()
+                    }
+                    None => "no previous release, the first release is a major bump".into(),
+                },
+                bump,
+                release_as: release_as.as_ref().map(Version::to_string),
+                commits,
+            });
+        }
+
+        let dependencies =
+            toml_file::read_dependencies_from_file(&config.repository_path, &directory)
+                .unwrap_or_else(|err| {
+                    error_exit!("Reading dependencies of {} failed: {}", package.name, err);
+                });
+
+        crates.push(planner::Crate {
+            package,
+            version,
+            previous_tag,
+            bump,
+            release_as,
+            history,
+            dependencies,
+        });
     }
-    let new_version = match version_bump(&version, bump) {
-        Some(new_version) => new_version.to_string(),
-        None => {
-            info_exit!("No version bump. Nothing to do.");
+
+    if let Some(format) = explain_format {
+        match format.as_str() {
+            "json" => println!("{}", explain::json(&explanations)),
+            _ => print!("{}", explain::table(&explanations)),
         }
-    };
+        exit(0);
+    }
+
+    let releases = planner::plan(&crates, |krate, bump| {
+        version::next_version(&krate.version, bump, channel.as_deref(), &krate.history)
+    })
+    .unwrap_or_else(|err| {
+        error_exit!("Planning the release failed: {}", err);
+    });
+    let requirement_updates = planner::requirement_updates(&crates, &releases);
+    let workspace_requirement_updates = planner::workspace_requirement_updates(&crates, &releases);
+
+    if releases.is_empty() {
+        info_exit!("No version bump. Nothing to do.");
+    }
+
+    if let Some(ref range) = range {
+        if let Some(release) = releases.iter().find(|r| !range.contains(&r.new_version)) {
+            error_exit!(
+                "Release {} of {} is outside of {}, the range of branch '{}'. \
+                 Release it from a branch that allows this version.",
+                release.new_version,
+                release.package.name,
+                range,
+                branch
+            );
+        }
+    }
+
+    // Crates inheriting the workspace version can only be released with the same version.
+    for (index, release) in releases.iter().enumerate() {
+        let conflict = releases[..index].iter().find(|other| {
+            other.package.version_location == release.package.version_location
+                && other.new_version != release.new_version
+        });
+        if let Some(other) = conflict {
+            error_exit!(
+                "{} and {} share the version in `{}`, but would be released as {} and {}",
+                other.package.name,
+                release.package.name,
+                release.package.version_location.manifest().display(),
+                other.new_version,
+                release.new_version
+            );
+        }
+    }
 
     if !config.write_mode {
-        let changelog = generate_changelog(&config.repository_path, &version, &new_version);
-        print_changelog(&changelog);
+        for (krate, release) in &requirement_updates {
+            info!(
+                "Would require {} {} in {}",
+                release.package.name, release.new_version, krate.package.name
+            );
+        }
+        for release in &workspace_requirement_updates {
+            info!(
+                "Would require {} {} in the workspace dependencies",
+                release.package.name, release.new_version
+            );
+        }
+
+        for release in &releases {
+            for file in config
+                .version_files
+                .iter()
+                .filter(|f| f.belongs_to(&release.package.name))
+            {
+                match file.check(&config.repository_path) {
+                    Ok(()) => info!(
+                        "Would write version {} to {}",
+                        release.new_version,
+                        file.path.display()
+                    ),
+                    Err(err) => warn!("{}", err),
+                }
+            }
+        }
+
+        for release in &releases {
+            let changelog = generate_changelog(&config, release);
+            print_changelog(&changelog);
+        }
+
+        if config.manifest.is_some() {
+            info!("Would write the following release manifest:");
+            println!(
+                "{}",
+                manifest::json(&release_manifests(&config, &releases, None))
+            );
+        }
     } else {
-        info!("New version: {}", new_version);
+        let mut updated_files = vec![];
+        for release in &releases {
+            info!("New version: {}", release.label());
+
+            toml_file::write_new_version(
+                &config.repository_path,
+                &release.package.version_location,
+                &release.new_version.to_string(),
+            )
+            .unwrap_or_else(|err| error!("Writing `Cargo.toml` failed: {}", err));
+
+            for file in config
+                .version_files
+                .iter()
+                .filter(|f| f.belongs_to(&release.package.name))
+            {
+                info!(
+                    "Writing version {} to {}",
+                    release.new_version,
+                    file.path.display()
+                );
+                match file.write(&config.repository_path, &release.new_version) {
+                    Ok(()) => updated_files.push(file.path.clone()),
+                    Err(err) => warn!("{}", err),
+                }
+            }
 
-        toml_file::write_new_version(&config.repository_path, &new_version)
-            .unwrap_or_else(|err| error!("Writing `Cargo.toml` failed: {:?}", err));
+            updated_files.extend(write_changelog(&config, release));
+        }
 
-        write_changelog(&config.repository_path, &version, &new_version);
-        package_crate(&config, &config.repository_path, &new_version);
+        for (krate, release) in &requirement_updates {
+            info!(
+                "Requiring {} {} in {}",
+                release.package.name, release.new_version, krate.package.name
+            );
+            let directory = krate.package.directory(&config.repository_path);
+            toml_file::write_dependency_version(
+                &directory,
+                &release.package.name,
+                &release.new_version,
+            )
+            .unwrap_or_else(|err| error!("Writing `Cargo.toml` failed: {}", err));
+            updated_files.push(krate.package.path.join("Cargo.toml"));
+        }
+        for release in &workspace_requirement_updates {
+            info!(
+                "Requiring {} {} in the workspace dependencies",
+                release.package.name, release.new_version
+            );
+            toml_file::write_workspace_dependency_version(
+                &config.repository_path,
+                &release.package.name,
+                &release.new_version,
+            )
+            .unwrap_or_else(|err| error!("Writing `Cargo.toml` failed: {}", err));
+            updated_files.push(PathBuf::from("Cargo.toml"));
+        }
 
-        info!("Creating annotated git tag");
-        let tag_message =
-            changelog::generate(&config.repository_path, &version.to_string(), &new_version)
-                .unwrap_or_else(|err| {
-                    error_exit!("Can't generate changelog: {:?}", err);
-                });
+        package_crates(&config, &releases, &updated_files);
+
+        let mut tag_names = vec![];
+        let mut tag_messages = vec![];
+        for release in &releases {
+            info!("Creating annotated git tag {}", release.tag_name());
+            let tag_message = changelog::generate(
+                &config.repository,
+                &config.changelog,
+                release,
+                &commit_filter(&config, &release.package),
+            )
+            .unwrap_or_else(|err| {
+                error_exit!("Can't generate changelog: {:?}", err);
+            });
 
-        let tag_name = format!("v{}", new_version);
-        git::tag(&config, &tag_name, &tag_message)
-            .unwrap_or_else(|err| error!("Failed to create git tag: {:?}", err));
+            let tag_name = release.tag_name();
+            git::tag(&config, &tag_name, &tag_message)
+                .unwrap_or_else(|err| error!("Failed to create git tag: {:?}", err));
+
+            tag_names.push(tag_name);
+            tag_messages.push(tag_message);
+        }
+
+        if let Some(ref path) = config.manifest {
+            let commit = config
+                .repository
+                .head()
+                .ok()
+                .and_then(|head| head.target())
+                .map(|oid| oid.to_string());
+            write_manifest(&config, path, &releases, commit);
+
+            if manifest_in_commit(&config, path) {
+                info!("Committing release manifest {}", path.display());
+                let new_versions = releases.iter().map(Release::label).collect::<Vec<_>>();
+                git::commit_manifest(&config, path, &new_versions).unwrap_or_else(|err| {
+                    error!("Committing the release manifest failed: {:?}", err)
+                });
+            }
+        }
 
         if config.release_mode && config.can_push() {
-            push_to_github(&config, &tag_name);
+            push_to_github(&config, &tag_names);
         }
 
         if config.release_mode && config.can_release_to_github() {
-            release_on_github(&config, &tag_message, &tag_name);
+            for (release, tag_message) in releases.iter().zip(&tag_messages) {
+                release_on_github(&config, release, tag_message);
+            }
         }
 
         if config.release_mode && config.can_release_to_cratesio() {
-            release_on_cratesio(&config);
-            info!(
-                "{} v{} is released. 🚀🚀🚀",
-                config.repository_name.unwrap(),
-                new_version
-            );
+            for release in &releases {
+                release_on_cratesio(&config, release);
+                info!(
+                    "{} v{} is released. 🚀🚀🚀",
+                    release.package.name, release.new_version
+                );
+            }
         }
     }
 }
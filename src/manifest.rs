@@ -0,0 +1,177 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use chrono::Utc;
+use git2::Repository;
+use serde::Serialize;
+
+use crate::commit_analyzer::CommitType;
+use crate::conventional_commit::ConventionalCommit;
+use crate::git::{self, CommitFilter};
+use crate::workspace::Release;
+
+/// A Conventional Commit of a release.
+#[derive(Debug, Serialize)]
+pub struct Change {
+    pub sha: String,
+    pub scope: Option<String>,
+    pub description: String,
+    pub body: Option<String>,
+    pub breaking: bool,
+    /// What breaks, from the description of a `!` commit or `BREAKING CHANGE` footers.
+    pub breaking_notes: Vec<String>,
+}
+
+/// A release as written to the JSON manifest, for tools that would
+/// otherwise have to read `Changelog.md`.
+#[derive(Debug, Serialize)]
+pub struct ReleaseManifest {
+    #[serde(rename = "crate")]
+    pub name: String,
+    pub version: String,
+    /// The version of the previous release, if there is one.
+    pub previous_version: Option<String>,
+    pub tag: String,
+    pub previous_tag: Option<String>,
+    /// The release commit, unknown when only explaining.
+    pub commit: Option<String>,
+    pub date: String,
+    pub bump: CommitType,
+    /// The Conventional Commits since the previous release by type, newest first.
+    pub commits: BTreeMap<String, Vec<Change>>,
+}
+
+/// Describes a release by the commits since the previous one that pass the filter.
+pub fn describe(
+    repo: &Repository,
+    release: &Release,
+    filter: &CommitFilter,
+    commit: Option<String>,
+) -> Result<ReleaseManifest, String> {
+    let commits =
+        git::commits_since(repo, release.previous_tag.as_deref(), filter).map_err(|err| {
+            format!(
+                "Reading the commits of {} failed: {}",
+                release.tag_name(),
+                err
+            )
+        })?;
+
+    let mut changes: BTreeMap<String, Vec<Change>> = BTreeMap::new();
+    for commit in &commits {
+        if let Ok(parsed) = ConventionalCommit::parse(commit.message().unwrap_or("")) {
+            let breaking_notes = parsed
+                .breaking_changes()
+                .into_iter()
+                .map(str::to_owned)
+                .collect();
+            changes
+                .entry(parsed.commit_type.clone())
+                .or_default()
+                .push(Change {
+                    sha: commit.id().to_string(),
+                    scope: parsed.scope,
+                    description: parsed.description,
+                    body: parsed.body,
+                    breaking: parsed.breaking,
+                    breaking_notes,
+                });
+        }
+    }
+
+    Ok(ReleaseManifest {
+        name: release.package.name.clone(),
+        version: release.new_version.to_string(),
+        previous_version: release
+            .previous_tag
+            .as_ref()
+            .and_then(|tag| release.package.tag_format.parse(&release.package.name, tag))
+            .map(|version| version.to_string()),
+        tag: release.tag_name(),
+        previous_tag: release.previous_tag.clone(),
+        commit,
+        date: Utc::now().format("%Y-%m-%d").to_string(),
+        bump: release.bump,
+        commits: changes,
+    })
+}
+
+/// Renders the manifests of all releases of a run as a JSON array.
+pub fn json(manifests: &[ReleaseManifest]) -> String {
+    serde_json::to_string_pretty(manifests).expect("Serializing the release manifest failed")
+}
+
+/// Writes the manifests to `path`, creating its directory if needed.
+pub fn write(path: &Path, manifests: &[ReleaseManifest]) -> Result<(), String> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)
+            .map_err(|err| format!("Creating {} failed: {}", directory.display(), err))?;
+    }
+    fs::write(path, json(manifests) + "\n")
+        .map_err(|err| format!("Writing {} failed: {}", path.display(), err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    use semver::Version;
+
+    use crate::tag_format::TagFormat;
+    use crate::test_repo::TempRepo;
+    use crate::toml_file::VersionLocation;
+    use crate::workspace::Package;
+
+    #[test]
+    fn describes_releases() {
+        let repo = TempRepo::new();
+
+        let root = repo.commit("feat: Initial release").create();
+        repo.tag_commit(root, "v1.0.0");
+        let feature = repo
+            .commit("feat(parser)!: Math mode\n\nBREAKING CHANGE: Drops `$`")
+            .create();
+        repo.commit("Update README").create();
+        let fix = repo.commit("fix: Into the void").create();
+
+        let release = Release {
+            package: Package {
+                name: "foo".into(),
+                path: PathBuf::new(),
+                nested_paths: vec![],
+                workspace_member: false,
+                tag_format: TagFormat::new("v{version}").unwrap(),
+                version_location: VersionLocation::Package(PathBuf::new()),
+            },
+            version: Version::parse("1.0.3").unwrap(),
+            new_version: Version::parse("2.0.0").unwrap(),
+            previous_tag: Some("v1.0.0".into()),
+            bump: CommitType::Major,
+        };
+        let manifest = describe(
+            &repo,
+            &release,
+            &CommitFilter::default(),
+            Some(fix.to_string()),
+        )
+        .unwrap();
+
+        let json: serde_json::Value = serde_json::from_str(&json(&[manifest])).unwrap();
+        assert_eq!(json[0]["crate"], "foo");
+        assert_eq!(json[0]["version"], "2.0.0");
+        assert_eq!(json[0]["previous_version"], "1.0.0");
+        assert_eq!(json[0]["tag"], "v2.0.0");
+        assert_eq!(json[0]["commit"], fix.to_string());
+        assert_eq!(json[0]["bump"], "major");
+        assert_eq!(json[0]["commits"]["fix"][0]["sha"], fix.to_string());
+        let feat = &json[0]["commits"]["feat"];
+        assert_eq!(feat.as_array().unwrap().len(), 1);
+        assert_eq!(feat[0]["sha"], feature.to_string());
+        assert_eq!(feat[0]["scope"], "parser");
+        assert_eq!(feat[0]["breaking"], true);
+        assert_eq!(feat[0]["breaking_notes"][0], "Drops `$`");
+    }
+}
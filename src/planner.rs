@@ -0,0 +1,335 @@
+use semver::Version;
+
+use crate::commit_analyzer::CommitType;
+use crate::toml_file::{DependencyKind, PathDependency};
+use crate::version::ReleaseHistory;
+use crate::workspace::{Package, Release};
+
+/// A crate of the repository, with the bump its own commits call for.
+pub struct Crate {
+    pub package: Package,
+    pub version: Version,
+    /// Tag of the previous release the bump was determined from.
+    pub previous_tag: Option<String>,
+    pub bump: CommitType,
+    /// Exact version to release, from `--release-as` or a `Release-As` footer.
+    pub release_as: Option<Version>,
+    pub history: ReleaseHistory,
+    pub dependencies: Vec<PathDependency>,
+}
+
+impl Crate {
+    /// Siblings this crate needs at build time, as indices into `crates`.
+    fn build_dependencies<'a>(&'a self, crates: &'a [Crate]) -> impl Iterator<Item = usize> + 'a {
+        self.dependencies
+            .iter()
+            .filter(|dependency| dependency.kind != DependencyKind::Development)
+            .filter_map(move |dependency| find(crates, &dependency.name))
+    }
+}
+
+fn find(crates: &[Crate], name: &str) -> Option<usize> {
+    crates.iter().position(|krate| krate.package.name == name)
+}
+
+/// Whether `new` is incompatible with `old` under Cargo's semver rules.
+pub fn is_breaking(old: &Version, new: &Version) -> bool {
+    if old.major != 0 {
+        new.major != old.major
+    } else if old.minor != 0 {
+        new.major != 0 || new.minor != old.minor
+    } else {
+        new != old
+    }
+}
+
+/// Orders the crates so that every crate comes after the siblings it depends on.
+///
+/// Dev-dependencies are ignored, they are not needed to publish a crate.
+pub fn publish_order(crates: &[Crate]) -> Result<Vec<usize>, String> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum State {
+        New,
+        Visiting,
+        Done,
+    }
+
+    fn visit(
+        crates: &[Crate],
+        index: usize,
+        states: &mut Vec<State>,
+        order: &mut Vec<usize>,
+    ) -> Result<(), String> {
+        match states[index] {
+            State::Done => return Ok(()),
+            State::Visiting => {
+                return Err(format!(
+                    "Dependency cycle involving {}",
+                    crates[index].package.name
+                ))
+            }
+            State::New => {}
+        }
+
+        states[index] = State::Visiting;
+        for dependency in crates[index].build_dependencies(crates) {
+            visit(crates, dependency, states, order)?;
+        }
+        states[index] = State::Done;
+        order.push(index);
+
+        Ok(())
+    }
+
+    let mut states = vec![State::New; crates.len()];
+    let mut order = Vec::with_capacity(crates.len());
+    for index in 0..crates.len() {
+        visit(crates, index, &mut states, &mut order)?;
+    }
+
+    Ok(order)
+}
+
+/// Determines the releases of this run, in publish order.
+///
+/// A crate whose sibling dependency gets a breaking new version is released
+/// as well, with at least a patch bump.
+pub fn plan<F>(crates: &[Crate], next_version: F) -> Result<Vec<Release>, String>
+where
+    F: Fn(&Crate, CommitType) -> Option<Version>,
+{
+    let order = publish_order(crates)?;
+    let mut new_versions: Vec<Option<Version>> = vec![None; crates.len()];
+    let mut bumps = vec![CommitType::Unknown; crates.len()];
+
+    for &index in &order {
+        let krate = &crates[index];
+        let mut bump = krate.bump;
+
+        let breaking_dependency = krate.build_dependencies(crates).find(|&dependency| {
+            matches!(&new_versions[dependency], Some(new) if is_breaking(&crates[dependency].version, new))
+        });
+
+        if let Some(dependency) = breaking_dependency {
+            if bump == CommitType::Unknown {
+                info!(
+                    "{} is released because its dependency {} has breaking changes",
+                    krate.package.name, crates[dependency].package.name
+                );
+                bump = CommitType::Patch;
+            }
+        }
+
+        bumps[index] = bump;
+        new_versions[index] = krate
+            .release_as
+            .clone()
+            .or_else(|| next_version(krate, bump));
+        if new_versions[index].is_none() {
+            info!("No version bump for {}", krate.package.name);
+        }
+    }
+
+    let releases = order
+        .into_iter()
+        .filter_map(|index| {
+            let krate = &crates[index];
+            new_versions[index].take().map(|new_version| Release {
+                package: krate.package.clone(),
+                version: krate.version.clone(),
+                previous_tag: krate.previous_tag.clone(),
+                new_version,
+                bump: bumps[index],
+            })
+        })
+        .collect();
+
+    Ok(releases)
+}
+
+/// Version requirements on released siblings that need to be rewritten,
+/// as `(dependent crate, released crate)` pairs.
+pub fn requirement_updates<'a>(
+    crates: &'a [Crate],
+    releases: &'a [Release],
+) -> Vec<(&'a Crate, &'a Release)> {
+    let mut updates = vec![];
+
+    for krate in crates {
+        for release in releases {
+            if requires(krate, release, false) {
+                updates.push((krate, release));
+            }
+        }
+    }
+
+    updates
+}
+
+/// Released siblings whose requirement in `[workspace.dependencies]` of the
+/// root manifest needs to be rewritten.
+pub fn workspace_requirement_updates<'a>(
+    crates: &[Crate],
+    releases: &'a [Release],
+) -> Vec<&'a Release> {
+    releases
+        .iter()
+        .filter(|release| crates.iter().any(|krate| requires(krate, release, true)))
+        .collect()
+}
+
+fn requires(krate: &Crate, release: &Release, inherited: bool) -> bool {
+    krate.dependencies.iter().any(|dependency| {
+        dependency.requirement.is_some()
+            && dependency.inherited == inherited
+            && dependency.name == release.package.name
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    use crate::tag_format::{self, TagFormat};
+    use crate::toml_file::VersionLocation;
+
+    fn krate(name: &str, version: &str, bump: CommitType, dependencies: &[&str]) -> Crate {
+        Crate {
+            package: Package {
+                name: name.into(),
+                path: PathBuf::from(name),
+                nested_paths: vec![],
+                workspace_member: true,
+                tag_format: TagFormat::new(tag_format::DEFAULT_WORKSPACE).unwrap(),
+                version_location: VersionLocation::Package(PathBuf::from(name)),
+            },
+            version: Version::parse(version).unwrap(),
+            previous_tag: None,
+            bump,
+            release_as: None,
+            history: ReleaseHistory {
+                latest_stable: None,
+                bump_since_stable: bump,
+                versions: vec![],
+            },
+            dependencies: dependencies
+                .iter()
+                .map(|name| PathDependency {
+                    name: name.to_string(),
+                    kind: DependencyKind::Normal,
+                    requirement: Some("1".into()),
+                    inherited: false,
+                })
+                .collect(),
+        }
+    }
+
+    fn bump(krate: &Crate, bump: CommitType) -> Option<Version> {
+        let mut version = krate.version.clone();
+        match bump {
+            CommitType::Unknown => return None,
+            CommitType::Patch => version.increment_patch(),
+            CommitType::Minor => version.increment_minor(),
+            CommitType::Major => version.increment_major(),
+        }
+        Some(version)
+    }
+
+    #[test]
+    fn orders_dependencies_first() {
+        let crates = vec![
+            krate("app", "1.0.0", CommitType::Patch, &["core", "util"]),
+            krate("util", "1.0.0", CommitType::Patch, &["core"]),
+            krate("core", "1.0.0", CommitType::Patch, &[]),
+        ];
+
+        assert_eq!(publish_order(&crates).unwrap(), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn detects_cycles() {
+        let crates = vec![
+            krate("a", "1.0.0", CommitType::Patch, &["b"]),
+            krate("b", "1.0.0", CommitType::Patch, &["a"]),
+        ];
+
+        assert!(publish_order(&crates).is_err());
+    }
+
+    #[test]
+    fn cascades_breaking_changes() {
+        let crates = vec![
+            krate("app", "1.0.0", CommitType::Unknown, &["util", "core"]),
+            krate("util", "1.0.0", CommitType::Unknown, &["core"]),
+            krate("core", "1.0.0", CommitType::Major, &[]),
+            krate("cli", "1.0.0", CommitType::Unknown, &["util"]),
+        ];
+
+        let releases = plan(&crates, bump).unwrap();
+        let releases = releases
+            .iter()
+            .map(|r| (&r.package.name[..], r.new_version.to_string()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            releases,
+            vec![
+                ("core", "2.0.0".into()),
+                ("util", "1.0.1".into()),
+                ("app", "1.0.1".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn compatible_bumps_do_not_cascade() {
+        let crates = vec![
+            krate("app", "1.0.0", CommitType::Unknown, &["core"]),
+            krate("core", "1.0.0", CommitType::Minor, &[]),
+        ];
+
+        let releases = plan(&crates, bump).unwrap();
+        assert_eq!(releases.len(), 1);
+        assert_eq!(releases[0].package.name, "core");
+
+        let updates = requirement_updates(&crates, &releases);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].0.package.name, "app");
+        assert!(workspace_requirement_updates(&crates, &releases).is_empty());
+
+        let mut crates = crates;
+        crates[0].dependencies[0].inherited = true;
+        assert!(requirement_updates(&crates, &releases).is_empty());
+        assert_eq!(workspace_requirement_updates(&crates, &releases).len(), 1);
+    }
+
+    #[test]
+    fn releases_requested_versions() {
+        let mut core = krate("core", "0.9.0", CommitType::Unknown, &[]);
+        core.release_as = Some(Version::parse("1.0.0").unwrap());
+        let crates = vec![krate("app", "1.0.0", CommitType::Unknown, &["core"]), core];
+
+        let releases = plan(&crates, bump).unwrap();
+        let releases = releases
+            .iter()
+            .map(|r| (&r.package.name[..], r.new_version.to_string()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            releases,
+            vec![("core", "1.0.0".into()), ("app", "1.0.1".into())]
+        );
+    }
+
+    #[test]
+    fn breaking_versions() {
+        let version = |v| Version::parse(v).unwrap();
+        assert!(is_breaking(&version("1.2.3"), &version("2.0.0")));
+        assert!(!is_breaking(&version("1.2.3"), &version("1.3.0")));
+        assert!(is_breaking(&version("0.2.3"), &version("0.3.0")));
+        assert!(!is_breaking(&version("0.2.3"), &version("0.2.4")));
+        assert!(is_breaking(&version("0.0.1"), &version("0.0.2")));
+    }
+}
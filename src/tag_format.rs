@@ -0,0 +1,101 @@
+use semver::Version;
+
+const CRATE: &str = "{crate}";
+const VERSION: &str = "{version}";
+
+pub const DEFAULT: &str = "v{version}";
+pub const DEFAULT_WORKSPACE: &str = "{crate}-v{version}";
+
+/// Template for the names of release tags, e.g. `v{version}`,
+/// `{crate}-v{version}` or `release/{version}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagFormat {
+    format: String,
+}
+
+impl TagFormat {
+    pub fn new(format: &str) -> Result<TagFormat, String> {
+        if format.matches(VERSION).count() != 1 {
+            return Err(format!(
+                "Tag format '{}' must contain {} exactly once",
+                format, VERSION
+            ));
+        }
+
+        Ok(TagFormat {
+            format: format.into(),
+        })
+    }
+
+    /// Whether tags of different crates can be told apart.
+    pub fn contains_crate(&self) -> bool {
+        self.format.contains(CRATE)
+    }
+
+    pub fn render(&self, crate_name: &str, version: &Version) -> String {
+        self.format
+            .replace(CRATE, crate_name)
+            .replace(VERSION, &version.to_string())
+    }
+
+    /// Extracts the version from a tag, if the tag belongs to the given crate.
+    pub fn parse(&self, crate_name: &str, tag: &str) -> Option<Version> {
+        let format = self.format.replace(CRATE, crate_name);
+        let version_start = format.find(VERSION)?;
+        let prefix = &format[..version_start];
+        let suffix = &format[version_start + VERSION.len()..];
+
+        tag.strip_prefix(prefix)
+            .and_then(|rest| rest.strip_suffix(suffix))
+            .and_then(|version| Version::parse(version).ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(version: &str) -> Version {
+        Version::parse(version).unwrap()
+    }
+
+    #[test]
+    fn renders_tags() {
+        let format = TagFormat::new(DEFAULT_WORKSPACE).unwrap();
+        assert_eq!(format.render("foo", &version("1.2.3")), "foo-v1.2.3");
+
+        let format = TagFormat::new("release/{version}").unwrap();
+        assert_eq!(format.render("foo", &version("1.2.3")), "release/1.2.3");
+    }
+
+    #[test]
+    fn parses_tags() {
+        let format = TagFormat::new(DEFAULT).unwrap();
+        assert_eq!(format.parse("foo", "v1.2.3"), Some(version("1.2.3")));
+        assert_eq!(format.parse("foo", "1.2.3"), None);
+
+        let format = TagFormat::new("{version}").unwrap();
+        assert_eq!(
+            format.parse("foo", "1.2.3-rc.1"),
+            Some(version("1.2.3-rc.1"))
+        );
+        assert_eq!(format.parse("foo", "v1.2.3"), None);
+    }
+
+    #[test]
+    fn parses_tags_of_crate() {
+        let format = TagFormat::new("{crate}@{version}-release").unwrap();
+        assert_eq!(
+            format.parse("foo", "foo@1.2.3-release"),
+            Some(version("1.2.3"))
+        );
+        assert_eq!(format.parse("foo", "bar@1.2.3-release"), None);
+        assert_eq!(format.parse("foo", "foo@1.2.3"), None);
+    }
+
+    #[test]
+    fn requires_version() {
+        assert!(TagFormat::new("{crate}").is_err());
+        assert!(TagFormat::new("{version}-{version}").is_err());
+    }
+}
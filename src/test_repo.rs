@@ -0,0 +1,149 @@
+//! Temporary git repositories for tests.
+
+use std::env;
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use git2::{Oid, Repository, Signature, Time};
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A repository in a fresh temporary directory, removed again on drop.
+pub struct TempRepo {
+    repo: Repository,
+    path: PathBuf,
+}
+
+impl TempRepo {
+    pub fn new() -> TempRepo {
+        let path = env::temp_dir().join(format!(
+            "semantic-rs-test-{}-{}",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        let _ = fs::remove_dir_all(&path);
+        let repo = Repository::init(&path).unwrap();
+        TempRepo { repo, path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Starts a commit with `message`, by default on HEAD without changes.
+    pub fn commit<'a>(&'a self, message: &'a str) -> CommitBuilder<'a> {
+        CommitBuilder {
+            repo: &self.repo,
+            message,
+            author: "semantic-rs",
+            files: &[],
+            parents: None,
+            time: None,
+        }
+    }
+
+    pub fn tag_commit(&self, oid: Oid, name: &str) {
+        let object = self.repo.find_object(oid, None).unwrap();
+        self.repo.tag_lightweight(name, &object, false).unwrap();
+    }
+}
+
+impl Deref for TempRepo {
+    type Target = Repository;
+
+    fn deref(&self) -> &Repository {
+        &self.repo
+    }
+}
+
+impl Drop for TempRepo {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+pub struct CommitBuilder<'a> {
+    repo: &'a Repository,
+    message: &'a str,
+    author: &'a str,
+    files: &'a [&'a str],
+    parents: Option<&'a [Oid]>,
+    time: Option<i64>,
+}
+
+impl<'a> CommitBuilder<'a> {
+    pub fn author(mut self, author: &'a str) -> Self {
+        self.author = author;
+        self
+    }
+
+    /// Writes the message into `files` and commits them.
+    pub fn files(mut self, files: &'a [&'a str]) -> Self {
+        self.files = files;
+        self
+    }
+
+    /// Commits on `parents` instead of HEAD, leaving HEAD alone.
+    pub fn parents(mut self, parents: &'a [Oid]) -> Self {
+        self.parents = Some(parents);
+        self
+    }
+
+    /// Seconds since the epoch, now if not set.
+    pub fn time(mut self, time: i64) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    pub fn create(self) -> Oid {
+        let repo = self.repo;
+        let mut index = repo.index().unwrap();
+        for file in self.files {
+            let path = repo.workdir().unwrap().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, self.message).unwrap();
+            index.add_path(Path::new(file)).unwrap();
+        }
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+
+        let email = "semantic-rs@example.com";
+        let signature = match self.time {
+            Some(time) => Signature::new(self.author, email, &Time::new(time, 0)),
+            None => Signature::now(self.author, email),
+        }
+        .unwrap();
+
+        let (update_ref, parents) = match self.parents {
+            Some(parents) => (
+                None,
+                parents
+                    .iter()
+                    .map(|oid| repo.find_commit(*oid).unwrap())
+                    .collect::<Vec<_>>(),
+            ),
+            None => (
+                Some("HEAD"),
+                repo.head()
+                    .ok()
+                    .map(|head| head.peel_to_commit().unwrap())
+                    .into_iter()
+                    .collect(),
+            ),
+        };
+        let parents = parents.iter().collect::<Vec<_>>();
+
+        repo.commit(
+            update_ref,
+            &signature,
+            &signature,
+            self.message,
+            &tree,
+            &parents,
+        )
+        .unwrap()
+    }
+}
@@ -1,56 +1,430 @@
-use std::fs::File;
-use std::fs::OpenOptions;
+use std::fmt;
+use std::fs::{self, File};
 use std::io::prelude::*;
 use std::io::Error;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use cargo_toml::Manifest;
-use regex::Regex;
+use semver::Version;
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use toml_edit::{Document, Item, TableLike, Value};
 
 #[derive(Debug)]
 pub enum TomlError {
     Parse(&'static str),
+    Deserialize(toml::de::Error),
+    Document(toml_edit::TomlError),
     Io(Error),
 }
 
-pub fn read_version(file: String) -> Option<String> {
-    let manifest: Manifest = match toml::from_str(&file) {
-        Ok(manifest) => manifest,
-        Err(_) => return None,
-    };
-    let package = match manifest.package {
-        Some(package) => package,
-        None => return None,
-    };
+impl fmt::Display for TomlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TomlError::Parse(message) => f.write_str(message),
+            TomlError::Deserialize(ref e) => e.fmt(f),
+            TomlError::Document(ref e) => e.fmt(f),
+            TomlError::Io(ref e) => e.fmt(f),
+        }
+    }
+}
+
+/// The parts of a `Cargo.toml` needed to find the crates of a repository.
+#[derive(Debug, Deserialize)]
+pub struct Manifest {
+    pub package: Option<ManifestPackage>,
+    pub workspace: Option<ManifestWorkspace>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ManifestPackage {
+    pub name: String,
+    pub version: Option<ManifestVersion>,
+}
+
+/// A package's `version`, either its own or inherited with `version.workspace = true`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ManifestVersion {
+    Inherited {
+        workspace: bool,
+    },
+    /// A version of its own, read from the document where it is needed.
+    Version(IgnoredAny),
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ManifestWorkspace {
+    pub members: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+/// Where the version of a crate is declared.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionLocation {
+    /// `[package] version` in the manifest of the crate in this directory,
+    /// relative to the repository root.
+    Package(PathBuf),
+    /// `[workspace.package] version` in the root manifest.
+    Workspace,
+}
+
+impl VersionLocation {
+    /// The manifest declaring the version, relative to the repository root.
+    pub fn manifest(&self) -> PathBuf {
+        match self {
+            VersionLocation::Package(directory) => directory.join("Cargo.toml"),
+            VersionLocation::Workspace => PathBuf::from("Cargo.toml"),
+        }
+    }
+}
+
+impl ManifestPackage {
+    /// Where the version of this package, in `directory`, is declared.
+    pub fn version_location(&self, directory: &Path) -> VersionLocation {
+        match self.version {
+            Some(ManifestVersion::Inherited { workspace: true }) => VersionLocation::Workspace,
+            _ => VersionLocation::Package(directory.to_owned()),
+        }
+    }
+}
+
+const PACKAGE_TABLE: &[&str] = &["package"];
+const WORKSPACE_PACKAGE_TABLE: &[&str] = &["workspace", "package"];
+
+fn table_version<'a>(item: &'a Item, table: &[&str]) -> Option<&'a str> {
+    table
+        .iter()
+        .try_fold(item, |item, key| item.get(key))?
+        .get("version")?
+        .as_str()
+}
+
+fn document_version(document: &Document, table: &[&str]) -> Option<String> {
+    table_version(document.as_item(), table)
+        .filter(|v| !v.is_empty())
+        .map(String::from)
+}
+
+pub fn read_version(file: String) -> Result<Option<String>, TomlError> {
+    Ok(document_version(&parse_document(&file)?, PACKAGE_TABLE))
+}
+
+pub fn read_workspace_version(file: &str) -> Result<Option<String>, TomlError> {
+    Ok(document_version(
+        &parse_document(file)?,
+        WORKSPACE_PACKAGE_TABLE,
+    ))
+}
+
+fn file_with_table_version(
+    file: &str,
+    table: &[&str],
+    new_version: &str,
+) -> Result<String, TomlError> {
+    let mut document = parse_document(file)?;
+
+    let version = table
+        .iter()
+        .try_fold(document.as_item_mut(), |item, key| item.get_mut(key))
+        .and_then(|table| table.get_mut("version"))
+        .filter(|version| version.is_str())
+        .ok_or(TomlError::Parse("No version field found"))?;
+    set_string(version, new_version);
+
+    Ok(render(&document, file))
+}
+
+/// Sets `[package] version`, keeping the rest of the file as it is.
+pub fn file_with_new_version(file: String, new_version: &str) -> Result<String, TomlError> {
+    file_with_table_version(&file, PACKAGE_TABLE, new_version)
+}
+
+/// Sets `[workspace.package] version`, keeping the rest of the file as it is.
+pub fn file_with_new_workspace_version(file: &str, new_version: &str) -> Result<String, TomlError> {
+    file_with_table_version(file, WORKSPACE_PACKAGE_TABLE, new_version)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DependencyKind {
+    Normal,
+    Development,
+    Build,
+}
+
+/// A dependency on another crate by `path`, e.g. a sibling in a workspace.
+#[derive(Debug)]
+pub struct PathDependency {
+    /// Name of the package, even if the dependency is renamed.
+    pub name: String,
+    pub kind: DependencyKind,
+    /// The version requirement, needed to publish the dependent crate.
+    pub requirement: Option<String>,
+    /// Declared with `workspace = true`, the requirement is in the root manifest.
+    pub inherited: bool,
+}
+
+const DEPENDENCY_TABLES: &[(&str, DependencyKind)] = &[
+    ("dependencies", DependencyKind::Normal),
+    ("dev-dependencies", DependencyKind::Development),
+    ("dev_dependencies", DependencyKind::Development),
+    ("build-dependencies", DependencyKind::Build),
+    ("build_dependencies", DependencyKind::Build),
+];
+
+/// Calls `f` for every dependency, including target-specific ones.
+fn for_each_dependency<F>(document: &mut Document, mut f: F)
+where
+    F: FnMut(DependencyKind, &str, &mut Item),
+{
+    fn visit<F>(table: &mut dyn TableLike, f: &mut F)
+    where
+        F: FnMut(DependencyKind, &str, &mut Item),
+    {
+        for &(name, kind) in DEPENDENCY_TABLES {
+            if let Some(dependencies) = table.get_mut(name).and_then(Item::as_table_like_mut) {
+                for (key, dependency) in dependencies.iter_mut() {
+                    f(kind, key.get(), dependency);
+                }
+            }
+        }
+    }
+
+    let root = document.as_table_mut();
+    visit(root, &mut f);
+
+    if let Some(targets) = root.get_mut("target").and_then(Item::as_table_like_mut) {
+        for (_, target) in targets.iter_mut() {
+            if let Some(target) = target.as_table_like_mut() {
+                visit(target, &mut f);
+            }
+        }
+    }
+}
+
+fn package_name(key: &str, dependency: &Item) -> String {
+    dependency
+        .get("package")
+        .and_then(Item::as_str)
+        .unwrap_or(key)
+        .to_owned()
+}
+
+/// Prints the document, ending like the file it was parsed from.
+pub fn render(document: &Document, file: &str) -> String {
+    let mut rendered = document.to_string();
+    if !file.ends_with('\n') && rendered.ends_with('\n') {
+        rendered.pop();
+    }
+    rendered
+}
+
+pub fn parse_document(file: &str) -> Result<Document, TomlError> {
+    file.parse::<Document>().map_err(TomlError::Document)
+}
+
+fn is_inherited(dependency: &Item) -> bool {
+    dependency.get("workspace").and_then(Item::as_bool) == Some(true)
+}
+
+/// Reads the `path` dependencies of a manifest. Dependencies declared with
+/// `workspace = true` are looked up in `[workspace.dependencies]` of the
+/// root manifest.
+pub fn read_path_dependencies(
+    file: &str,
+    root_file: &str,
+) -> Result<Vec<PathDependency>, TomlError> {
+    let mut document = parse_document(file)?;
+    let root = parse_document(root_file)?;
+    let workspace_dependencies = root
+        .get("workspace")
+        .and_then(|workspace| workspace.get("dependencies"));
+    let mut dependencies = vec![];
+
+    for_each_dependency(&mut document, |kind, key, dependency| {
+        let inherited = is_inherited(dependency);
+        let dependency = if inherited {
+            match workspace_dependencies.and_then(|dependencies| dependencies.get(key)) {
+                Some(dependency) => dependency,
+                None => return,
+            }
+        } else {
+            &*dependency
+        };
+        if dependency.get("path").is_none() {
+            return;
+        }
+
+        dependencies.push(PathDependency {
+            name: package_name(key, dependency),
+            kind,
+            requirement: dependency
+                .get("version")
+                .and_then(Item::as_str)
+                .map(String::from),
+            inherited,
+        });
+    });
+
+    Ok(dependencies)
+}
+
+/// Keeps the operator of a simple requirement like `^1.2` or `=1.2.0`, but requires the new version.
+///
+/// Compound requirements such as `>=1.0, <2.0` are left alone.
+fn updated_requirement(requirement: &str, new_version: &Version) -> Option<String> {
+    if requirement.contains(',') || requirement.contains('*') {
+        return None;
+    }
 
-    Some(package.version).filter(|v| !v.is_empty())
+    let version_start = requirement.find(|c: char| c.is_ascii_digit())?;
+    Some(format!(
+        "{}{}",
+        requirement[..version_start].trim(),
+        new_version
+    ))
 }
 
-pub fn file_with_new_version(file: String, new_version: &str) -> String {
-    let re = Regex::new(r#"version\s=\s"\d+\.\d+\.\d+""#).unwrap();
-    let new_version = format!("version = \"{}\"", new_version);
-    re.replace(&file, &new_version[..]).to_string()
+/// Replaces a string value without touching the surrounding formatting.
+pub fn replace_string(value: &mut Value, new_value: &str) {
+    let decor = value.decor().clone();
+    *value = Value::from(new_value);
+    *value.decor_mut() = decor;
 }
 
-pub fn read_from_file(repository_path: &str) -> Result<String, TomlError> {
-    let file_path = Path::new(&repository_path).join("Cargo.toml");
-    let cargo_file = match read_cargo_toml(&file_path) {
-        Ok(buffer) => buffer,
-        Err(err) => return Err(TomlError::Io(err)),
+fn set_string(item: &mut Item, new_value: &str) {
+    if let Some(value) = item.as_value_mut() {
+        replace_string(value, new_value);
+    }
+}
+
+/// Requires `new_version` in a path dependency on the package `name`.
+fn update_requirement(key: &str, dependency: &mut Item, name: &str, new_version: &Version) {
+    if dependency.get("path").is_none() || package_name(key, dependency) != name {
+        return;
+    }
+
+    let requirement = match dependency
+        .as_table_like_mut()
+        .and_then(|t| t.get_mut("version"))
+    {
+        Some(requirement) => requirement,
+        None => return,
     };
 
-    match read_version(cargo_file) {
-        Some(version) => Ok(version),
-        None => Err(TomlError::Parse("No version field found")),
+    match requirement
+        .as_str()
+        .and_then(|old| updated_requirement(old, new_version))
+    {
+        Some(new_requirement) => set_string(requirement, &new_requirement),
+        None => warn!(
+            "Version requirement on {} is not a simple version, leaving it unchanged",
+            name
+        ),
+    }
+}
+
+/// Updates the version requirement of every path dependency on the package `name`.
+pub fn file_with_dependency_version(
+    file: &str,
+    name: &str,
+    new_version: &Version,
+) -> Result<String, TomlError> {
+    let mut document = parse_document(file)?;
+
+    for_each_dependency(&mut document, |_, key, dependency| {
+        update_requirement(key, dependency, name, new_version)
+    });
+
+    Ok(render(&document, file))
+}
+
+/// Updates the version requirement on the package `name` in `[workspace.dependencies]`.
+pub fn file_with_workspace_dependency_version(
+    file: &str,
+    name: &str,
+    new_version: &Version,
+) -> Result<String, TomlError> {
+    let mut document = parse_document(file)?;
+
+    let dependencies = document
+        .get_mut("workspace")
+        .and_then(|workspace| workspace.get_mut("dependencies"))
+        .and_then(Item::as_table_like_mut);
+    if let Some(dependencies) = dependencies {
+        for (key, dependency) in dependencies.iter_mut() {
+            update_requirement(key.get(), dependency, name, new_version);
+        }
     }
+
+    Ok(render(&document, file))
+}
+
+pub fn read_dependencies_from_file(
+    repository_path: &str,
+    directory: &str,
+) -> Result<Vec<PathDependency>, TomlError> {
+    let file_path = Path::new(directory).join("Cargo.toml");
+    let cargo_toml = read_cargo_toml(&file_path).map_err(TomlError::Io)?;
+    let root_path = Path::new(repository_path).join("Cargo.toml");
+    let root_toml = read_cargo_toml(&root_path).map_err(TomlError::Io)?;
+    read_path_dependencies(&cargo_toml, &root_toml)
 }
 
-pub fn write_new_version(repository_path: &str, new_version: &str) -> Result<(), Error> {
-    let file_path = Path::new(&repository_path).join("Cargo.toml");
-    let cargo_toml = read_cargo_toml(&file_path)?;
-    let new_cargo_toml = file_with_new_version(cargo_toml, new_version);
-    let mut handle = OpenOptions::new().read(true).write(true).open(file_path)?;
-    handle.write_all(new_cargo_toml.as_bytes())
+pub fn write_dependency_version(
+    directory: &str,
+    name: &str,
+    new_version: &Version,
+) -> Result<(), TomlError> {
+    let file_path = Path::new(directory).join("Cargo.toml");
+    let cargo_toml = read_cargo_toml(&file_path).map_err(TomlError::Io)?;
+    let new_cargo_toml = file_with_dependency_version(&cargo_toml, name, new_version)?;
+    fs::write(file_path, new_cargo_toml).map_err(TomlError::Io)
+}
+
+/// Updates the requirement on `name` in `[workspace.dependencies]` of the root manifest.
+pub fn write_workspace_dependency_version(
+    repository_path: &str,
+    name: &str,
+    new_version: &Version,
+) -> Result<(), TomlError> {
+    let file_path = Path::new(repository_path).join("Cargo.toml");
+    let cargo_toml = read_cargo_toml(&file_path).map_err(TomlError::Io)?;
+    let new_cargo_toml = file_with_workspace_dependency_version(&cargo_toml, name, new_version)?;
+    fs::write(file_path, new_cargo_toml).map_err(TomlError::Io)
+}
+
+pub fn read_manifest(directory: &Path) -> Result<Manifest, TomlError> {
+    let cargo_file = read_cargo_toml(&directory.join("Cargo.toml")).map_err(TomlError::Io)?;
+    toml::from_str(&cargo_file).map_err(TomlError::Deserialize)
+}
+
+pub fn read_from_file(
+    repository_path: &str,
+    location: &VersionLocation,
+) -> Result<String, TomlError> {
+    let file_path = Path::new(repository_path).join(location.manifest());
+    let cargo_file = read_cargo_toml(&file_path).map_err(TomlError::Io)?;
+
+    let version = match location {
+        VersionLocation::Package(_) => read_version(cargo_file)?,
+        VersionLocation::Workspace => read_workspace_version(&cargo_file)?,
+    };
+    version.ok_or(TomlError::Parse("No version field found"))
+}
+
+pub fn write_new_version(
+    repository_path: &str,
+    location: &VersionLocation,
+    new_version: &str,
+) -> Result<(), TomlError> {
+    let file_path = Path::new(repository_path).join(location.manifest());
+    let cargo_toml = read_cargo_toml(&file_path).map_err(TomlError::Io)?;
+    let new_cargo_toml = match location {
+        VersionLocation::Package(_) => file_with_new_version(cargo_toml, new_version)?,
+        VersionLocation::Workspace => file_with_new_workspace_version(&cargo_toml, new_version)?,
+    };
+    fs::write(file_path, new_cargo_toml).map_err(TomlError::Io)
 }
 
 fn read_cargo_toml(file_path: &Path) -> Result<String, Error> {
@@ -68,9 +442,6 @@ fn read_cargo_toml(file_path: &Path) -> Result<String, Error> {
 
 #[cfg(test)]
 mod tests {
-    extern crate regex;
-    extern crate toml;
-
     use super::*;
 
     fn example_file() -> String {
@@ -96,19 +467,29 @@ mod tests {
 
     #[test]
     fn read_version_number() {
-        let version_str = read_version(example_file());
+        let version_str = read_version(example_file()).unwrap();
         assert_eq!(version_str, Some("0.1.0".into()));
     }
 
     #[test]
     fn read_file_without_version_number() {
-        let version_str = read_version(example_file_without_version());
+        let version_str = read_version(example_file_without_version()).unwrap();
         assert_eq!(version_str, None);
     }
 
+    #[test]
+    fn report_syntax_errors() {
+        let err = parse_document("[package]\nversion = 0.1.0\n").unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{}", err);
+        assert!(file_with_new_version("[package".into(), "0.2.0")
+            .unwrap_err()
+            .to_string()
+            .contains("line 1"));
+    }
+
     #[test]
     fn write_new_version_number() {
-        let new_toml_file = file_with_new_version(example_file(), "0.2.0");
+        let new_toml_file = file_with_new_version(example_file(), "0.2.0").unwrap();
         let expected_file = "[package]
     name = \"semantic-rs\"
     version = \"0.2.0\"
@@ -119,4 +500,141 @@ mod tests {
             .to_string();
         assert_eq!(new_toml_file, expected_file);
     }
+
+    #[test]
+    fn write_only_package_version() {
+        let file = "[dependencies]
+foo = { version = \"1.0.0\" }
+
+[package]
+name = \"foo\"
+version=\"1.0.0-rc.1\" # keep me
+";
+        let new_file = file_with_new_version(file.to_string(), "1.0.0").unwrap();
+        assert_eq!(
+            new_file,
+            file.replace("version=\"1.0.0-rc.1\"", "version=\"1.0.0\"")
+        );
+    }
+
+    #[test]
+    fn inherited_workspace_version() {
+        let member = "[package]
+name = \"bar\"
+version.workspace = true
+";
+        let manifest: Manifest = toml::from_str(member).unwrap();
+        let location = manifest.package.unwrap().version_location(Path::new("bar"));
+        assert_eq!(location, VersionLocation::Workspace);
+        assert_eq!(location.manifest(), PathBuf::from("Cargo.toml"));
+        assert_eq!(read_version(member.to_string()).unwrap(), None);
+        assert!(file_with_new_version(member.to_string(), "1.0.0").is_err());
+
+        let root = "[workspace]
+members = [\"bar\"]
+
+[workspace.package]
+version = \"0.4.0\"
+";
+        assert_eq!(read_workspace_version(root).unwrap(), Some("0.4.0".into()));
+        assert_eq!(
+            file_with_new_workspace_version(root, "0.5.0").unwrap(),
+            root.replace("0.4.0", "0.5.0")
+        );
+    }
+
+    fn workspace_member_file() -> String {
+        "[package]
+name = \"bar\"
+version = \"0.3.0\"
+
+[dependencies]
+foo = { path = \"../foo\", version = \"1.2\" } # sibling
+serde = \"1.0\"
+
+[dependencies.renamed]
+package = \"baz\"
+path = \"../baz\"
+version = \"=0.1.0\"
+
+[target.'cfg(unix)'.build-dependencies]
+qux = { path = \"../qux\" }
+"
+        .to_string()
+    }
+
+    #[test]
+    fn read_path_dependencies_of_member() {
+        let dependencies = read_path_dependencies(&workspace_member_file(), "").unwrap();
+        let dependencies = dependencies
+            .iter()
+            .map(|d| (&d.name[..], d.kind, d.requirement.as_deref()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            dependencies,
+            vec![
+                ("foo", DependencyKind::Normal, Some("1.2")),
+                ("baz", DependencyKind::Normal, Some("=0.1.0")),
+                ("qux", DependencyKind::Build, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn read_inherited_path_dependencies() {
+        let root = "[workspace]
+members = [\"foo\", \"bar\"]
+
+[workspace.dependencies]
+foo = { path = \"foo\", version = \"1.2\" }
+serde = \"1.0\"
+";
+        let member = "[package]
+name = \"bar\"
+version = \"0.3.0\"
+
+[dependencies]
+foo = { workspace = true }
+serde.workspace = true
+";
+        let dependencies = read_path_dependencies(member, root).unwrap();
+        assert_eq!(dependencies.len(), 1);
+        assert_eq!(dependencies[0].name, "foo");
+        assert_eq!(dependencies[0].requirement.as_deref(), Some("1.2"));
+        assert!(dependencies[0].inherited);
+
+        let new_version = Version::parse("1.3.0").unwrap();
+        assert_eq!(
+            file_with_workspace_dependency_version(root, "foo", &new_version).unwrap(),
+            root.replace("\"1.2\"", "\"1.3.0\"")
+        );
+        assert_eq!(
+            file_with_dependency_version(member, "foo", &new_version).unwrap(),
+            member
+        );
+    }
+
+    #[test]
+    fn write_dependency_versions() {
+        let new_version = Version::parse("1.3.0").unwrap();
+        let file = file_with_dependency_version(&workspace_member_file(), "foo", &new_version);
+        let new_version = Version::parse("0.2.0").unwrap();
+        let file = file_with_dependency_version(&file.unwrap(), "baz", &new_version).unwrap();
+
+        let expected_file = workspace_member_file()
+            .replace("version = \"1.2\"", "version = \"1.3.0\"")
+            .replace("version = \"=0.1.0\"", "version = \"=0.2.0\"");
+        assert_eq!(file, expected_file);
+    }
+
+    #[test]
+    fn keep_compound_requirements() {
+        let new_version = Version::parse("2.0.0").unwrap();
+        assert_eq!(
+            updated_requirement("^1.2", &new_version),
+            Some("^2.0.0".into())
+        );
+        assert_eq!(updated_requirement(">=1.0, <2.0", &new_version), None);
+    }
 }
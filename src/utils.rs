@@ -39,6 +39,36 @@ pub fn user_repo_from_url(url: &str) -> Result<(String, String), String> {
     Ok((user, repo))
 }
 
+/// The host of a remote URL, like `github.com` for `git@github.com:user/repo.git`.
+pub fn host_from_url(url: &str) -> Option<String> {
+    match Url::parse(url) {
+        Ok(url) => url.host_str().map(str::to_owned),
+        Err(ParseError::RelativeUrlWithoutBase) => {
+            let host = &url[..url.find(':')?];
+            let host = host.rsplit('@').next().unwrap_or(host);
+            Some(host.to_owned()).filter(|host| !host.is_empty())
+        }
+        Err(_) => None,
+    }
+}
+
+/// Matches `name` against a pattern with `*` and `?` wildcards.
+pub fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+
+    fn matches(pattern: &[char], name: &[char]) -> bool {
+        match pattern.split_first() {
+            None => name.is_empty(),
+            Some(('*', rest)) => (0..=name.len()).any(|skip| matches(rest, &name[skip..])),
+            Some(('?', rest)) => !name.is_empty() && matches(rest, &name[1..]),
+            Some((c, rest)) => name.first() == Some(c) && matches(rest, &name[1..]),
+        }
+    }
+
+    matches(&pattern, &name)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -93,4 +123,26 @@ mod test {
             assert!(user_repo_from_url(url).is_err());
         }
     }
+
+    #[test]
+    fn parses_hosts() {
+        let urls = [
+            ("https://github.com/user/repo.git", "github.com"),
+            ("git@gitlab.example.com:user/repo.git", "gitlab.example.com"),
+            ("ssh://git@codeberg.org/user/repo", "codeberg.org"),
+        ];
+
+        for &(url, host) in &urls {
+            assert_eq!(host_from_url(url).as_deref(), Some(host));
+        }
+    }
+
+    #[test]
+    fn matches_wildcards() {
+        assert!(wildcard_match("*", "foo"));
+        assert!(wildcard_match("foo-*", "foo-bar"));
+        assert!(wildcard_match("foo-?ar", "foo-bar"));
+        assert!(!wildcard_match("foo-*", "bar-foo"));
+        assert!(!wildcard_match("foo-?", "foo-"));
+    }
 }
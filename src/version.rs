@@ -0,0 +1,275 @@
+use std::cmp;
+use std::fmt;
+
+use semver::{Identifier, Version};
+
+use crate::commit_analyzer::CommitType;
+
+/// What the tags tell about the previous releases of a crate.
+pub struct ReleaseHistory {
+    /// The latest release without pre-release identifiers.
+    pub latest_stable: Option<Version>,
+    /// Bump for all commits since the latest stable release.
+    pub bump_since_stable: CommitType,
+    /// Every version that has been tagged.
+    pub versions: Vec<Version>,
+}
+
+fn version_bump(version: &Version, bump: CommitType) -> Option<Version> {
+    let mut version = version.clone();
+
+    // NB: According to the Semver spec, major version zero is for
+    // the initial development phase is treated slightly differently.
+    // The minor version is incremented for breaking changes
+    // and major is kept at zero until the public API has become more stable.
+    if version.major == 0 {
+        match bump {
+            CommitType::Unknown => return None,
+            CommitType::Patch => version.increment_patch(),
+            CommitType::Minor => version.increment_patch(),
+            CommitType::Major => version.increment_minor(),
+        }
+    } else {
+        match bump {
+            CommitType::Unknown => return None,
+            CommitType::Patch => version.increment_patch(),
+            CommitType::Minor => version.increment_minor(),
+            CommitType::Major => version.increment_major(),
+        }
+    }
+
+    Some(version)
+}
+
+#[test]
+fn test_breaking_bump_major_zero() {
+    let buggy_release = Version::parse("0.2.0").unwrap();
+    let bumped_version = version_bump(&buggy_release, CommitType::Major).unwrap();
+    assert_eq!(bumped_version, Version::parse("0.3.0").unwrap());
+}
+
+#[test]
+fn test_breaking_bump_major_one() {
+    let buggy_release = Version::parse("1.0.0").unwrap();
+    let bumped_version = version_bump(&buggy_release, CommitType::Major).unwrap();
+    assert_eq!(bumped_version, Version::parse("2.0.0").unwrap());
+}
+
+/// The versions a maintenance branch may release, like `1.x` or `1.4.x`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionRange {
+    major: u64,
+    minor: Option<u64>,
+}
+
+impl VersionRange {
+    pub fn parse(range: &str) -> Result<VersionRange, String> {
+        let invalid = || format!("'{}' is not a version range like 1.x or 1.4.x", range);
+        let number = |part: &str| part.parse::<u64>().map_err(|_| invalid());
+
+        match range.split('.').collect::<Vec<_>>()[..] {
+            [major, "x"] => Ok(VersionRange {
+                major: number(major)?,
+                minor: None,
+            }),
+            [major, minor, "x"] => Ok(VersionRange {
+                major: number(major)?,
+                minor: Some(number(minor)?),
+            }),
+            _ => Err(invalid()),
+        }
+    }
+
+    pub fn contains(&self, version: &Version) -> bool {
+        version.major == self.major && self.minor.map_or(true, |minor| version.minor == minor)
+    }
+}
+
+impl fmt::Display for VersionRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.minor {
+            Some(minor) => write!(f, "{}.{}.x", self.major, minor),
+            None => write!(f, "{}.x", self.major),
+        }
+    }
+}
+
+/// The version of a crate's first release, when it should not be a major
+/// bump of the version in `Cargo.toml`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InitialVersion {
+    /// The version already in `Cargo.toml`.
+    Current,
+    Version(Version),
+}
+
+impl InitialVersion {
+    pub fn parse(initial: &str) -> Result<InitialVersion, String> {
+        match initial {
+            "current" => Ok(InitialVersion::Current),
+            _ => Version::parse(initial)
+                .map(InitialVersion::Version)
+                .map_err(|_| {
+                    format!(
+                        "'{}' is not an initial version like 1.0.0 or current",
+                        initial
+                    )
+                }),
+        }
+    }
+
+    /// The version to release, given the version in `Cargo.toml`.
+    pub fn resolve(&self, current: &Version) -> Version {
+        match self {
+            InitialVersion::Current => current.clone(),
+            InitialVersion::Version(version) => version.clone(),
+        }
+    }
+}
+
+/// Computes the version of the next release, on the given pre-release channel if any.
+///
+/// Pre-releases are based on the latest stable release, taking all changes since then
+/// into account. Repeated pre-releases only increase their counter, e.g. `1.3.0-beta.2`,
+/// until a bigger change moves the base version, e.g. to `2.0.0-beta.1`.
+pub fn next_version(
+    current: &Version,
+    bump: CommitType,
+    channel: Option<&str>,
+    history: &ReleaseHistory,
+) -> Option<Version> {
+    if bump == CommitType::Unknown {
+        return None;
+    }
+
+    let mut version = if channel.is_some() || current.is_prerelease() {
+        let bump = cmp::max(bump, history.bump_since_stable);
+        match history.latest_stable {
+            Some(ref stable) => version_bump(stable, bump)?,
+            None if current.is_prerelease() => stable_part(current),
+            None => version_bump(current, bump)?,
+        }
+    } else {
+        version_bump(current, bump)?
+    };
+
+    if let Some(channel) = channel {
+        let counter = history
+            .versions
+            .iter()
+            .filter(|released| stable_part(released) == version)
+            .filter_map(|released| prerelease_counter(released, channel))
+            .max()
+            .map_or(1, |counter| counter + 1);
+
+        version.pre = vec![
+            Identifier::AlphaNumeric(channel.into()),
+            Identifier::Numeric(counter),
+        ];
+    }
+
+    Some(version)
+}
+
+fn stable_part(version: &Version) -> Version {
+    Version::new(version.major, version.minor, version.patch)
+}
+
+/// The `N` of a pre-release like `1.3.0-beta.N`.
+fn prerelease_counter(version: &Version, channel: &str) -> Option<u64> {
+    match &version.pre[..] {
+        [Identifier::AlphaNumeric(name), Identifier::Numeric(counter)] if name == channel => {
+            Some(*counter)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+fn history(
+    latest_stable: &str,
+    bump_since_stable: CommitType,
+    versions: &[&str],
+) -> ReleaseHistory {
+    ReleaseHistory {
+        latest_stable: Some(Version::parse(latest_stable).unwrap()),
+        bump_since_stable,
+        versions: versions
+            .iter()
+            .map(|v| Version::parse(v).unwrap())
+            .collect(),
+    }
+}
+
+#[test]
+fn test_first_prerelease() {
+    let current = Version::parse("1.2.0").unwrap();
+    let history = history("1.2.0", CommitType::Minor, &["1.2.0"]);
+    let version = next_version(&current, CommitType::Minor, Some("beta"), &history);
+    assert_eq!(version, Some(Version::parse("1.3.0-beta.1").unwrap()));
+}
+
+#[test]
+fn test_next_prerelease_keeps_base() {
+    let current = Version::parse("1.3.0-beta.2").unwrap();
+    let history = history(
+        "1.2.0",
+        CommitType::Minor,
+        &["1.2.0", "1.3.0-beta.1", "1.3.0-beta.2", "1.3.0-alpha.5"],
+    );
+    let version = next_version(&current, CommitType::Patch, Some("beta"), &history);
+    assert_eq!(version, Some(Version::parse("1.3.0-beta.3").unwrap()));
+}
+
+#[test]
+fn test_prerelease_moves_base() {
+    let current = Version::parse("1.3.0-beta.2").unwrap();
+    let history = history(
+        "1.2.0",
+        CommitType::Major,
+        &["1.3.0-beta.1", "1.3.0-beta.2"],
+    );
+    let version = next_version(&current, CommitType::Major, Some("beta"), &history);
+    assert_eq!(version, Some(Version::parse("2.0.0-beta.1").unwrap()));
+}
+
+#[test]
+fn test_stable_release_after_prerelease() {
+    let current = Version::parse("1.3.0-beta.2").unwrap();
+    let history = history(
+        "1.2.0",
+        CommitType::Minor,
+        &["1.3.0-beta.1", "1.3.0-beta.2"],
+    );
+    let version = next_version(&current, CommitType::Minor, None, &history);
+    assert_eq!(version, Some(Version::parse("1.3.0").unwrap()));
+}
+
+#[test]
+fn test_version_ranges() {
+    let range = VersionRange::parse("1.x").unwrap();
+    assert!(range.contains(&Version::parse("1.5.0").unwrap()));
+    assert!(!range.contains(&Version::parse("2.0.0").unwrap()));
+
+    let range = VersionRange::parse("1.4.x").unwrap();
+    assert!(range.contains(&Version::parse("1.4.2").unwrap()));
+    assert!(!range.contains(&Version::parse("1.5.0").unwrap()));
+    assert_eq!(range.to_string(), "1.4.x");
+
+    assert!(VersionRange::parse("master").is_err());
+    assert!(VersionRange::parse("1.x.x").is_err());
+}
+
+#[test]
+fn test_initial_versions() {
+    let current = Version::parse("0.3.0").unwrap();
+    assert_eq!(
+        InitialVersion::parse("current").unwrap().resolve(&current),
+        current
+    );
+    assert_eq!(
+        InitialVersion::parse("1.0.0").unwrap().resolve(&current),
+        Version::parse("1.0.0").unwrap()
+    );
+    assert!(InitialVersion::parse("1.0").is_err());
+}
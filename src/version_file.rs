@@ -0,0 +1,219 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use regex::{Captures, Regex};
+use semver::Version;
+
+/// Matches a full version, like `1.2.3` or `2.0.0-beta.1`.
+const VERSION: &str = r"[0-9]+\.[0-9]+\.[0-9]+(?:-[0-9A-Za-z.-]+)?(?:\+[0-9A-Za-z.-]+)?";
+
+const PLACEHOLDERS: &[&str] = &["{version}", "{major}", "{minor}", "{patch}"];
+
+/// A file besides `Cargo.toml` that mentions the version of a crate, like a
+/// dependency line in `README.md` or the `html_root_url` in `lib.rs`.
+#[derive(Debug, Clone)]
+pub struct VersionFile {
+    /// The file, relative to the repository root.
+    pub path: PathBuf,
+    /// The crate whose version the file mentions, the only crate if not set.
+    pub crate_name: Option<String>,
+    /// The pattern as configured, for messages.
+    source: String,
+    regex: Regex,
+    /// The text written for a match, with placeholders for the new version.
+    /// Without one, the first group of the match is replaced by the version.
+    template: Option<String>,
+}
+
+impl VersionFile {
+    /// A pattern like `foo = "{major}.{minor}"`, matching the text with any
+    /// version and written with the new one.
+    pub fn with_placeholders(
+        path: PathBuf,
+        crate_name: Option<String>,
+        pattern: &str,
+    ) -> Result<VersionFile, String> {
+        if !PLACEHOLDERS.iter().any(|p| pattern.contains(p)) {
+            return Err(format!(
+                "The pattern '{}' for {} has no placeholder like {{version}}",
+                pattern,
+                path.display()
+            ));
+        }
+
+        let mut regex = String::new();
+        let mut rest = pattern;
+        while let Some(start) = rest.find('{') {
+            regex.push_str(&regex::escape(&rest[..start]));
+            rest = &rest[start..];
+            match PLACEHOLDERS.iter().find(|p| rest.starts_with(*p)) {
+                Some(placeholder) => {
+                    regex.push_str(if *placeholder == "{version}" {
+                        VERSION
+                    } else {
+                        "[0-9]+"
+                    });
+                    rest = &rest[placeholder.len()..];
+                }
+                None => {
+                    regex.push_str(r"\{");
+                    rest = &rest[1..];
+                }
+            }
+        }
+        regex.push_str(&regex::escape(rest));
+
+        Ok(VersionFile {
+            path,
+            crate_name,
+            source: pattern.to_owned(),
+            regex: Regex::new(&regex).expect("Building the pattern failed"),
+            template: Some(pattern.to_owned()),
+        })
+    }
+
+    /// A regular expression whose first group, or whole match without one,
+    /// is the version.
+    pub fn with_regex(
+        path: PathBuf,
+        crate_name: Option<String>,
+        regex: &str,
+    ) -> Result<VersionFile, String> {
+        let compiled = Regex::new(regex)
+            .map_err(|err| format!("Invalid pattern for {}: {}", path.display(), err))?;
+
+        Ok(VersionFile {
+            path,
+            crate_name,
+            source: regex.to_owned(),
+            regex: compiled,
+            template: None,
+        })
+    }
+
+    /// Whether the file mentions the version of `crate_name`.
+    pub fn belongs_to(&self, crate_name: &str) -> bool {
+        self.crate_name
+            .as_ref()
+            .map_or(true, |name| name == crate_name)
+    }
+
+    /// Writes `version` into every match in `text`, None if nothing matches.
+    fn update(&self, text: &str, version: &Version) -> Option<String> {
+        if !self.regex.is_match(text) {
+            return None;
+        }
+
+        let updated = self.regex.replace_all(text, |captures: &Captures| {
+            let whole = captures.get(0).unwrap();
+            match (&self.template, captures.get(1)) {
+                (Some(template), _) => template
+                    .replace("{version}", &version.to_string())
+                    .replace("{major}", &version.major.to_string())
+                    .replace("{minor}", &version.minor.to_string())
+                    .replace("{patch}", &version.patch.to_string()),
+                (None, Some(group)) => format!(
+                    "{}{}{}",
+                    &text[whole.start()..group.start()],
+                    version,
+                    &text[group.end()..whole.end()]
+                ),
+                (None, None) => version.to_string(),
+            }
+        });
+        Some(updated.into_owned())
+    }
+
+    fn read(&self, repository_path: &str) -> Result<String, String> {
+        fs::read_to_string(Path::new(repository_path).join(&self.path))
+            .map_err(|err| format!("Reading {} failed: {}", self.path.display(), err))
+    }
+
+    fn not_found(&self) -> String {
+        format!(
+            "'{}' not found in {}, its version is not updated",
+            self.source,
+            self.path.display()
+        )
+    }
+
+    /// Checks that the pattern still matches, for a dry run.
+    pub fn check(&self, repository_path: &str) -> Result<(), String> {
+        if self.regex.is_match(&self.read(repository_path)?) {
+            Ok(())
+        } else {
+            Err(self.not_found())
+        }
+    }
+
+    /// Writes the new version into the file. Fails if the pattern does not
+    /// match, so the file does not silently go stale.
+    pub fn write(&self, repository_path: &str, version: &Version) -> Result<(), String> {
+        let text = self.read(repository_path)?;
+        let updated = self
+            .update(&text, version)
+            .ok_or_else(|| self.not_found())?;
+        fs::write(Path::new(repository_path).join(&self.path), updated)
+            .map_err(|err| format!("Writing {} failed: {}", self.path.display(), err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(version: &str) -> Version {
+        Version::parse(version).unwrap()
+    }
+
+    #[test]
+    fn updates_placeholders() {
+        let file = VersionFile::with_placeholders(
+            PathBuf::from("README.md"),
+            None,
+            r#"semantic-rs = "{major}.{minor}""#,
+        )
+        .unwrap();
+        let readme = "[dependencies]\nsemantic-rs = \"1.0\"\nother = \"1.0\"\n";
+
+        assert_eq!(
+            file.update(readme, &version("1.2.0")).as_deref(),
+            Some("[dependencies]\nsemantic-rs = \"1.2\"\nother = \"1.0\"\n")
+        );
+        assert_eq!(file.update("semantic-rs = \"*\"", &version("1.2.0")), None);
+
+        let file =
+            VersionFile::with_placeholders(PathBuf::from("a"), None, "v{version} {x}").unwrap();
+        assert_eq!(
+            file.update("v1.0.0-beta.2 {x}", &version("1.0.0"))
+                .as_deref(),
+            Some("v1.0.0 {x}")
+        );
+        assert!(VersionFile::with_placeholders(PathBuf::from("a"), None, "version").is_err());
+    }
+
+    #[test]
+    fn updates_regex_groups() {
+        let file = VersionFile::with_regex(
+            PathBuf::from("src/lib.rs"),
+            Some("foo".into()),
+            r#"html_root_url = "https://docs.rs/foo/([^"]+)""#,
+        )
+        .unwrap();
+        let lib = "#![doc(html_root_url = \"https://docs.rs/foo/1.0.0\")]\n";
+
+        assert_eq!(
+            file.update(lib, &version("1.1.0")).as_deref(),
+            Some("#![doc(html_root_url = \"https://docs.rs/foo/1.1.0\")]\n")
+        );
+        assert!(file.belongs_to("foo") && !file.belongs_to("bar"));
+
+        let file =
+            VersionFile::with_regex(PathBuf::from("VERSION"), None, r"\d+\.\d+\.\d+").unwrap();
+        assert_eq!(
+            file.update("1.0.0\n", &version("2.0.0")).as_deref(),
+            Some("2.0.0\n")
+        );
+        assert!(VersionFile::with_regex(PathBuf::from("a"), None, "(").is_err());
+    }
+}
@@ -0,0 +1,292 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use semver::Version;
+
+use crate::commit_analyzer::CommitType;
+use crate::tag_format::{self, TagFormat};
+use crate::toml_file::{self, TomlError, VersionLocation};
+use crate::utils::wildcard_match;
+
+/// A crate that semantic-rs releases.
+#[derive(Debug, Clone)]
+pub struct Package {
+    pub name: String,
+    /// The crate's directory, relative to the repository root.
+    pub path: PathBuf,
+    /// Directories of other crates inside this one's, like the members below
+    /// the root package. Their changes don't count towards this crate.
+    pub nested_paths: Vec<PathBuf>,
+    /// Whether the crate is part of a Cargo workspace.
+    pub workspace_member: bool,
+    pub tag_format: TagFormat,
+    pub version_location: VersionLocation,
+}
+
+impl Package {
+    /// Absolute path of the crate's directory.
+    pub fn directory(&self, repository_path: &str) -> String {
+        Path::new(repository_path)
+            .join(&self.path)
+            .to_str()
+            .expect("Path is not valid unicode")
+            .to_owned()
+    }
+
+    pub fn tag_name(&self, version: &Version) -> String {
+        self.tag_format.render(&self.name, version)
+    }
+}
+
+/// A crate that gets a new version in this run.
+pub struct Release {
+    pub package: Package,
+    pub version: Version,
+    pub new_version: Version,
+    pub previous_tag: Option<String>,
+    /// The bump the new version was determined with.
+    pub bump: CommitType,
+}
+
+impl Release {
+    pub fn tag_name(&self) -> String {
+        self.package.tag_name(&self.new_version)
+    }
+
+    /// Short description used in log output and the release commit.
+    pub fn label(&self) -> String {
+        if self.package.workspace_member {
+            format!("{} {}", self.package.name, self.new_version)
+        } else {
+            self.new_version.to_string()
+        }
+    }
+}
+
+/// Finds all crates in the repository.
+///
+/// For a plain crate this is the root package. For a workspace it is every
+/// member (minus the excluded ones) and, if the root manifest is not virtual,
+/// the root package as well.
+///
+/// A workspace whose only package is the root one counts as a plain crate.
+/// Without a configured tag format single crates are tagged `v1.2.3`
+/// and workspace members `name-v1.2.3`.
+pub fn packages(
+    repository_path: &str,
+    tag_format: Option<&TagFormat>,
+) -> Result<Vec<Package>, TomlError> {
+    let root = Path::new(repository_path);
+    let manifest = toml_file::read_manifest(root)?;
+
+    let default_format = |format| TagFormat::new(format).expect("Invalid default tag format");
+
+    let single_crate = |package: toml_file::ManifestPackage| {
+        vec![Package {
+            version_location: package.version_location(Path::new("")),
+            name: package.name,
+            path: PathBuf::new(),
+            nested_paths: vec![],
+            workspace_member: false,
+            tag_format: tag_format
+                .cloned()
+                .unwrap_or_else(|| default_format(tag_format::DEFAULT)),
+        }]
+    };
+
+    let workspace = match manifest.workspace {
+        Some(workspace) => workspace,
+        None => {
+            let package = manifest
+                .package
+                .ok_or(TomlError::Parse("No package found"))?;
+            return Ok(single_crate(package));
+        }
+    };
+
+    // The root package is added below, even if it is listed as a member.
+    let members = member_dirs(root, &workspace.members, &workspace.exclude)?
+        .into_iter()
+        .filter(|path| path.as_os_str() != "")
+        .collect::<Vec<_>>();
+
+    // A workspace around just the root package is still a single crate.
+    let package = match (manifest.package, members.is_empty()) {
+        (Some(package), true) => return Ok(single_crate(package)),
+        (package, _) => package,
+    };
+
+    let tag_format = tag_format
+        .cloned()
+        .unwrap_or_else(|| default_format(tag_format::DEFAULT_WORKSPACE));
+
+    let mut packages = vec![];
+    if let Some(package) = package {
+        packages.push(Package {
+            version_location: package.version_location(Path::new("")),
+            name: package.name,
+            path: PathBuf::new(),
+            nested_paths: vec![],
+            workspace_member: true,
+            tag_format: tag_format.clone(),
+        });
+    }
+
+    for path in members {
+        let member = toml_file::read_manifest(&root.join(&path))?;
+        let package = member
+            .package
+            .ok_or(TomlError::Parse("Workspace member has no package"))?;
+        packages.push(Package {
+            version_location: package.version_location(&path),
+            name: package.name,
+            path,
+            nested_paths: vec![],
+            workspace_member: true,
+            tag_format: tag_format.clone(),
+        });
+    }
+
+    if packages.is_empty() {
+        return Err(TomlError::Parse("Workspace has no members"));
+    }
+
+    let paths = packages
+        .iter()
+        .map(|package| package.path.clone())
+        .collect::<Vec<_>>();
+    for package in &mut packages {
+        package.nested_paths = paths
+            .iter()
+            .filter(|path| **path != package.path && path.starts_with(&package.path))
+            .cloned()
+            .collect();
+    }
+
+    Ok(packages)
+}
+
+fn member_dirs(
+    root: &Path,
+    members: &[String],
+    exclude: &[String],
+) -> Result<Vec<PathBuf>, TomlError> {
+    let excluded = exclude
+        .iter()
+        .map(|path| normalize(path))
+        .collect::<Vec<_>>();
+    let mut dirs: Vec<PathBuf> = vec![];
+
+    for member in members {
+        for dir in expand_member(root, member)? {
+            if !excluded.contains(&dir) && !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+    }
+
+    Ok(dirs)
+}
+
+/// Expands a `members` entry, which may contain `*` and `?` wildcards.
+fn expand_member(root: &Path, pattern: &str) -> Result<Vec<PathBuf>, TomlError> {
+    let mut candidates = vec![PathBuf::new()];
+
+    for segment in segments(pattern) {
+        let mut next = vec![];
+
+        for candidate in candidates {
+            if !is_wildcard(segment) {
+                next.push(candidate.join(segment));
+                continue;
+            }
+
+            let entries = match fs::read_dir(root.join(&candidate)) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+
+            for entry in entries {
+                let entry = entry.map_err(TomlError::Io)?;
+                let is_dir = entry.file_type().map_err(TomlError::Io)?.is_dir();
+                let name = entry.file_name();
+                match name.to_str() {
+                    Some(name) if is_dir && wildcard_match(segment, name) => {
+                        next.push(candidate.join(name))
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        candidates = next;
+    }
+
+    // Like cargo, only directories with a manifest count as members of a glob.
+    if is_wildcard(pattern) {
+        candidates.retain(|dir| root.join(dir).join("Cargo.toml").is_file());
+    }
+    candidates.sort();
+
+    Ok(candidates)
+}
+
+fn segments(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|s| !s.is_empty() && *s != ".")
+}
+
+fn normalize(path: &str) -> PathBuf {
+    segments(path).collect()
+}
+
+fn is_wildcard(pattern: &str) -> bool {
+    pattern.contains('*') || pattern.contains('?')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_repo::TempRepo;
+
+    #[test]
+    fn normalizes_member_paths() {
+        assert_eq!(normalize("./crates/foo/"), PathBuf::from("crates/foo"));
+    }
+
+    #[test]
+    fn root_only_workspace_is_a_single_crate() {
+        let repo = TempRepo::new();
+        let manifest = "[package]\nname = \"solo\"\nversion = \"1.0.0\"\n\n[workspace]\n";
+        fs::write(repo.path().join("Cargo.toml"), manifest).unwrap();
+        let root = repo.path().to_str().unwrap();
+
+        let found = packages(root, None).unwrap();
+        assert_eq!(found.len(), 1);
+        assert!(!found[0].workspace_member);
+        assert_eq!(
+            found[0].tag_name(&Version::parse("1.1.0").unwrap()),
+            "v1.1.0"
+        );
+
+        let manifest = format!("{}members = [\".\", \"foo\"]\n", manifest);
+        fs::write(repo.path().join("Cargo.toml"), manifest).unwrap();
+        fs::create_dir(repo.path().join("foo")).unwrap();
+        fs::write(
+            repo.path().join("foo/Cargo.toml"),
+            "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+
+        let found = packages(root, None).unwrap();
+        assert_eq!(
+            found
+                .iter()
+                .map(|p| p.tag_name(&Version::parse("1.1.0").unwrap()))
+                .collect::<Vec<_>>(),
+            vec!["solo-v1.1.0", "foo-v1.1.0"]
+        );
+        assert_eq!(found[0].nested_paths, vec![PathBuf::from("foo")]);
+        assert!(found[1].nested_paths.is_empty());
+    }
+}